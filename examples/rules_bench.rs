@@ -0,0 +1,125 @@
+//! Times the rule solver on large puzzles:
+//!
+//! cargo run --release --example rules_bench [SIZE]...
+//!
+//! Puzzles are striped N x N grids: even rows are black and odd rows are split
+//! into islands of 1 to 4 cells by single black cells. Each size is solved
+//! from its clues only, then loaded and solved with 70% of the solution
+//! revealed, which marks most of the cells up front.
+
+use std::{env, time::Instant};
+
+use nurikabe::nurikabe::Nurikabe;
+use nurikabe::solvers::state::{BLACK, UNKNOWN, WHITE};
+use nurikabe::solvers::{rng::Rng, NaiveSolver, Solver, Step};
+
+const SIZES: [usize; 4] = [30, 60, 100, 150];
+
+/// Share of the solution given with the clues.
+const REVEALED: f64 = 0.7;
+
+/// Returns the solution and the puzzle of a striped grid.
+///
+fn striped(n: usize, seed: u64) -> (Vec<i32>, Vec<i32>) {
+    let mut rng = Rng::new(seed);
+    let mut solution = vec![BLACK; n * n];
+    let mut puzzle = vec![UNKNOWN; n * n];
+
+    for x in (1..n).step_by(2) {
+        let mut y = rng.random_int(0..2);
+
+        while y < n {
+            let len = (1 + rng.random_int(0..4)).min(n - y);
+            for k in 0..len {
+                solution[x * n + y + k] = WHITE;
+            }
+            puzzle[x * n + y + rng.random_int(0..len)] = len as i32;
+            y += len + 1;
+        }
+    }
+
+    (solution, puzzle)
+}
+
+/// Runs the rules until they stop. Returns the time in ms, the number of
+/// iterations and the cells left unknown.
+///
+fn run(n: usize, data: Vec<i32>) -> (f64, usize, usize) {
+    let start = Instant::now();
+    let mut solver = NaiveSolver::new(Nurikabe::new(n, n, data));
+
+    let step = loop {
+        let step = solver.solve();
+        if step != Step::Proceed {
+            break step;
+        }
+    };
+    let ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    assert_ne!(step, Step::Contradiction, "Striped puzzle has a solution.");
+    let unknown = solver
+        .get_state()
+        .data
+        .iter()
+        .filter(|&&v| v == UNKNOWN)
+        .count();
+
+    (ms, solver.get_iteration(), unknown)
+}
+
+fn main() -> Result<(), String> {
+    let sizes = env::args()
+        .skip(1)
+        .map(|arg| arg.parse().map_err(|_| format!("Invalid size: {}", arg)))
+        .collect::<Result<Vec<usize>, _>>()?;
+    let sizes = if sizes.is_empty() {
+        SIZES.to_vec()
+    } else {
+        sizes
+    };
+
+    for n in sizes {
+        let name = format!("{}x{}", n, n);
+        let (solution, puzzle) = striped(n, n as u64);
+
+        let (ms, iterations, unknown) = run(n, puzzle.clone());
+        println!(
+            "{:<8} clues only:   {:>9.1} ms, {:>4} iterations, {:>5} unknown left",
+            name, ms, iterations, unknown
+        );
+
+        let mut rng = Rng::new(7);
+        let revealed = puzzle
+            .iter()
+            .zip(solution.iter())
+            .map(|(&clue, &cell)| match clue {
+                1.. => clue,
+                _ if rng.random_float() < REVEALED => cell,
+                _ => UNKNOWN,
+            })
+            .collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let solver = NaiveSolver::new(Nurikabe::new(n, n, revealed.clone()));
+        let ms = start.elapsed().as_secs_f64() * 1000.0;
+        drop(solver);
+        println!(
+            "{:<8} load {:.0}%:     {:>9.1} ms",
+            name,
+            REVEALED * 100.0,
+            ms
+        );
+
+        let (ms, iterations, unknown) = run(n, revealed);
+        println!(
+            "{:<8} {:.0}% revealed: {:>9.1} ms, {:>4} iterations, {:>5} unknown left",
+            name,
+            REVEALED * 100.0,
+            ms,
+            iterations,
+            unknown
+        );
+    }
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 import init, {
   startup,
   load,
+  puzzle_url,
   // sum_of_squares,
   // sum_of_squares_simple,
 } from "./pkg/nurikabe.js";
@@ -18,27 +19,58 @@ async function run_wasm() {
     await update_grid(path);
   };
 
-  async function update_grid(path) {
-    let nurikabe = await from_file(path);
+  document.getElementById("puzzle_url").onchange = async (_e) => {
+    let url = document.getElementById("puzzle_url").value.trim();
+    if (url) {
+      await update_grid(url, url);
+    }
+  };
+
+  async function update_grid(path, raw_input) {
+    let nurikabe = raw_input ? from_text(path, raw_input) : await from_file(path);
+    if (!nurikabe) {
+      return;
+    }
+
     window.nurikabe = nurikabe;
     window.previous = null;
     restart_grid();
   }
 
   async function from_file(path) {
-    let nurikabe = {};
+    let nurikabe = null;
 
     await fetch(path)
       .then((res) => res.text())
-      .then((raw_input) => {
-        nurikabe = load(raw_input);
-        nurikabe.raw_input = raw_input;
-        nurikabe.path = path;
-        nurikabe.duration = 1;
-        nurikabe.iteration = 0;
-        nurikabe.solved = false;
-      })
-      .catch((e) => console.error(e));
+      .then((raw_input) => (nurikabe = from_text(path, raw_input)))
+      .catch((e) => {
+        console.error(e);
+        document.getElementById("progress").innerText = "Can't load puzzle: " + e;
+      });
+
+    return nurikabe;
+  }
+
+  // Parses a file or a pasted link. Shows the error and returns null when the
+  // input isn't a puzzle.
+  function from_text(path, raw_input) {
+    let nurikabe = null;
+
+    try {
+      nurikabe = load(raw_input);
+    } catch (e) {
+      console.error(e);
+      document.getElementById("progress").innerText = "Can't load puzzle: " + e;
+      return null;
+    }
+
+    nurikabe.raw_input = raw_input;
+    nurikabe.path = path;
+    nurikabe.duration = 1;
+    nurikabe.iteration = 0;
+    nurikabe.solved = false;
+    document.getElementById("progress").innerText = "";
+    document.getElementById("puzzle_url").value = puzzle_url(nurikabe);
 
     return nurikabe;
   }
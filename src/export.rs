@@ -0,0 +1,499 @@
+use std::{fmt::Write, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::nurikabe::Nurikabe;
+use crate::solvers::state::{BLACK, WHITE};
+use crate::solvers::{for_valid_neighbours, sat::Encoding};
+use crate::verify::verify;
+
+/// Input format of external solvers.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// DIMACS CNF for SAT solvers, with a `VarMap` sidecar.
+    Dimacs,
+    /// SMT-LIB 2 for SMT solvers.
+    SmtLib,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dimacs" => Ok(Format::Dimacs),
+            "smt2" => Ok(Format::SmtLib),
+            _ => Err(format!("Unknown format: {}", s)),
+        }
+    }
+}
+
+/// Sidecar of a DIMACS file, says what the variables mean. Variables are
+/// numbered from 1 as in the DIMACS file, the ones not listed are auxiliary
+/// (island sizes and connectivity).
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VarMap {
+    pub width: usize,
+    pub height: usize,
+    /// Puzzle data, clues and `0`.
+    pub puzzle: Vec<i32>,
+    pub num_vars: usize,
+    /// Variable of every cell, row by row, true when the cell is black.
+    pub cells: Vec<usize>,
+    /// Variables of white cells being part of an island.
+    pub members: Vec<Member>,
+}
+
+/// Variable that is true when the cell belongs to the island of the clue.
+/// Positions are (row, column).
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Member {
+    pub var: usize,
+    pub cell: (usize, usize),
+    pub clue: (usize, usize),
+}
+
+/// Puzzle as a DIMACS CNF file and its variable map. Every model of the
+/// clauses is a solution of the puzzle.
+///
+pub fn to_dimacs(nurikabe: &Nurikabe) -> (String, VarMap) {
+    let mut encoding = Encoding::new(nurikabe);
+    encoding.connect();
+
+    let width = nurikabe.width;
+    let pos = |i: usize| (i / width, i % width);
+
+    let mut members = vec![];
+    for i in 0..width * nurikabe.height {
+        for &(k, var) in encoding.members(i) {
+            members.push(Member {
+                var: var + 1,
+                cell: pos(i),
+                clue: pos(encoding.islands()[k].0),
+            });
+        }
+    }
+
+    let map = VarMap {
+        width,
+        height: nurikabe.height,
+        puzzle: nurikabe.data.iter().map(|&v| v.max(0)).collect(),
+        num_vars: encoding.num_vars,
+        cells: (1..=width * nurikabe.height).collect(),
+        members,
+    };
+
+    let mut cnf = String::new();
+    let _ = writeln!(
+        cnf,
+        "c nurikabe {} {}x{}",
+        nurikabe.path, width, nurikabe.height
+    );
+    let _ = writeln!(
+        cnf,
+        "c variables 1 to {} are the cells, true is black",
+        map.cells.len()
+    );
+    let _ = writeln!(
+        cnf,
+        "p cnf {} {}",
+        encoding.num_vars,
+        encoding.clauses.len()
+    );
+
+    for clause in encoding.clauses.iter() {
+        for lit in clause.iter() {
+            let var = lit.var() as i64 + 1;
+            let _ = write!(cnf, "{} ", if lit.is_positive() { var } else { -var });
+        }
+        cnf.push_str("0\n");
+    }
+
+    (cnf, map)
+}
+
+/// Grid of a model printed by a SAT solver for the DIMACS file of `map`. Takes
+/// the `v` lines of the competition format or plain lists of literals.
+///
+pub fn from_dimacs(map: &VarMap, model: &str) -> Result<Nurikabe, String> {
+    let mut values = vec![None; map.num_vars + 1];
+
+    for line in model.lines() {
+        let line = line.trim();
+
+        if line.starts_with("s UNSAT") || line == "UNSAT" {
+            return Err(String::from("Solver found no model"));
+        }
+        if line.is_empty() || line.starts_with('c') || line.starts_with('s') || line == "SAT" {
+            continue;
+        }
+
+        for token in line.trim_start_matches('v').split_whitespace() {
+            let lit = token
+                .parse::<i64>()
+                .map_err(|_| format!("Invalid literal in model: {}", token))?;
+
+            let var = lit.unsigned_abs() as usize;
+            if var > map.num_vars {
+                return Err(format!("Unknown variable in model: {}", var));
+            }
+            if var > 0 {
+                values[var] = Some(lit > 0);
+            }
+        }
+    }
+
+    let mut data = Vec::with_capacity(map.cells.len());
+    for (&var, &clue) in map.cells.iter().zip(map.puzzle.iter()) {
+        let black = values[var].ok_or_else(|| format!("Model misses variable: {}", var))?;
+
+        data.push(match clue {
+            clue if clue > 0 => clue,
+            _ if black => BLACK,
+            _ => WHITE,
+        });
+    }
+
+    let puzzle = Nurikabe::new(map.width, map.height, map.puzzle.clone());
+    Ok(checked(&puzzle, data))
+}
+
+/// Puzzle as an SMT-LIB 2 script in `QF_LIA`. Cell `b_<row>_<col>` is true when
+/// black. Islands and the river are connected with integer distances to their
+/// clue and to a single root cell.
+///
+pub fn to_smtlib(nurikabe: &Nurikabe) -> String {
+    let (width, height) = (nurikabe.width, nurikabe.height);
+    let name = |prefix: &str, i: usize| format!("{}_{}_{}", prefix, i / width, i % width);
+    let black = |i: usize| name("b", i);
+    let neighbours = |i: usize| {
+        let mut neighbours = vec![];
+        for_valid_neighbours(width, height, i / width, i % width, |x, y| {
+            neighbours.push(x * width + y)
+        });
+        neighbours
+    };
+
+    let clues = nurikabe
+        .data
+        .iter()
+        .enumerate()
+        .filter(|(_, &v)| v > 0)
+        .map(|(i, &v)| (i, v as usize))
+        .collect::<Vec<_>>();
+    let white_cells = clues.iter().map(|&(_, size)| size).sum::<usize>();
+    let cells = 0..width * height;
+
+    let mut smt = String::new();
+    let _ = writeln!(smt, "; nurikabe {} {}x{}", nurikabe.path, width, height);
+    smt.push_str("(set-logic QF_LIA)\n");
+
+    for i in cells.clone() {
+        let _ = writeln!(smt, "(declare-const {} Bool)", black(i));
+        let _ = writeln!(smt, "(declare-const {} Int)", name("island", i));
+        let _ = writeln!(smt, "(declare-const {} Int)", name("dist", i));
+    }
+
+    // Clues and island labels, white neighbours share the label.
+    for (k, &(i, _)) in clues.iter().enumerate() {
+        let _ = writeln!(
+            smt,
+            "(assert (and (not {}) (= {} {}) (= {} 0)))",
+            black(i),
+            name("island", i),
+            k,
+            name("dist", i)
+        );
+    }
+    for i in cells.clone() {
+        let _ = writeln!(
+            smt,
+            "(assert (=> (not {}) (and (<= 0 {island}) (< {island} {}))))",
+            black(i),
+            clues.len(),
+            island = name("island", i)
+        );
+
+        for j in neighbours(i).into_iter().filter(|&j| j > i) {
+            let _ = writeln!(
+                smt,
+                "(assert (=> (and (not {}) (not {})) (= {} {})))",
+                black(i),
+                black(j),
+                name("island", i),
+                name("island", j)
+            );
+        }
+    }
+
+    // Island sizes.
+    for (k, &(_, size)) in clues.iter().enumerate() {
+        let terms = cells
+            .clone()
+            .map(|i| {
+                format!(
+                    "(ite (and (not {}) (= {} {})) 1 0)",
+                    black(i),
+                    name("island", i),
+                    k
+                )
+            })
+            .collect::<Vec<_>>();
+        let _ = writeln!(smt, "(assert (= (+ 0 {}) {}))", terms.join(" "), size);
+    }
+
+    // Island cells are one step further from the clue than a neighbour.
+    for i in cells.clone().filter(|&i| nurikabe.data[i] <= 0) {
+        let dist = name("dist", i);
+        let steps = neighbours(i)
+            .into_iter()
+            .map(|j| {
+                format!(
+                    "(and (not {}) (= {} (- {} 1)))",
+                    black(j),
+                    name("dist", j),
+                    dist
+                )
+            })
+            .collect::<Vec<_>>();
+        let _ = writeln!(
+            smt,
+            "(assert (=> (not {}) (and (> {} 0) (or {}))))",
+            black(i),
+            dist,
+            steps.join(" ")
+        );
+    }
+
+    // No 2x2 black blocks.
+    for x in 0..height.saturating_sub(1) {
+        for y in 0..width.saturating_sub(1) {
+            let i = x * width + y;
+            let _ = writeln!(
+                smt,
+                "(assert (not (and {} {} {} {})))",
+                black(i),
+                black(i + 1),
+                black(i + width),
+                black(i + width + 1)
+            );
+        }
+    }
+
+    // River has a single root, every other black cell is one step further
+    // from it than a black neighbour.
+    if width * height > white_cells {
+        let roots = cells
+            .clone()
+            .map(|i| format!("(ite (and {} (= {} 0)) 1 0)", black(i), name("dist", i)))
+            .collect::<Vec<_>>();
+        let _ = writeln!(smt, "(assert (= (+ 0 {}) 1))", roots.join(" "));
+
+        for i in cells.clone() {
+            let dist = name("dist", i);
+            let steps = neighbours(i)
+                .into_iter()
+                .map(|j| format!("(and {} (= {} (- {} 1)))", black(j), name("dist", j), dist))
+                .collect::<Vec<_>>();
+            let _ = writeln!(
+                smt,
+                "(assert (=> {} (and (>= {dist} 0) (or (= {dist} 0) {}))))",
+                black(i),
+                steps.join(" "),
+                dist = dist
+            );
+        }
+    }
+
+    smt.push_str("(check-sat)\n(get-model)\n");
+    smt
+}
+
+/// Grid of a model printed by an SMT solver for the script of `to_smtlib`.
+///
+pub fn from_smtlib(puzzle: &Nurikabe, model: &str) -> Result<Nurikabe, String> {
+    let spaced = model.replace('(', " ( ").replace(')', " ) ");
+    let tokens = spaced.split_whitespace().collect::<Vec<_>>();
+
+    if tokens.first() == Some(&"unsat") {
+        return Err(String::from("Solver found no model"));
+    }
+
+    let width = puzzle.width;
+    let mut values = vec![None; width * puzzle.height];
+
+    for k in 0..tokens.len() {
+        // define-fun b_<row>_<col> ( ) Bool <value>
+        if tokens[k] != "define-fun" || k + 6 >= tokens.len() || tokens[k + 4] != "Bool" {
+            continue;
+        }
+
+        let Some(pos) = tokens[k + 1].strip_prefix("b_") else {
+            continue;
+        };
+        let (x, y) = pos
+            .split_once('_')
+            .and_then(|(x, y)| Some((x.parse::<usize>().ok()?, y.parse::<usize>().ok()?)))
+            .ok_or_else(|| format!("Unknown constant in model: {}", tokens[k + 1]))?;
+
+        if x >= puzzle.height || y >= width {
+            return Err(format!("Unknown constant in model: {}", tokens[k + 1]));
+        }
+
+        values[x * width + y] = match tokens[k + 5] {
+            "true" => Some(true),
+            "false" => Some(false),
+            value => return Err(format!("Invalid value in model: {}", value)),
+        };
+    }
+
+    let mut data = Vec::with_capacity(values.len());
+    for (i, (value, &clue)) in values.iter().zip(puzzle.data.iter()).enumerate() {
+        let black = value.ok_or_else(|| format!("Model misses b_{}_{}", i / width, i % width))?;
+
+        data.push(match clue {
+            clue if clue > 0 => clue,
+            _ if black => BLACK,
+            _ => WHITE,
+        });
+    }
+
+    Ok(checked(puzzle, data))
+}
+
+/// Grid with `solved` set when it's a valid solution of the puzzle.
+///
+fn checked(puzzle: &Nurikabe, data: Vec<i32>) -> Nurikabe {
+    let mut result = Nurikabe::new(puzzle.width, puzzle.height, data);
+    result.path = puzzle.path.clone();
+    result.solved = verify(puzzle, &result).is_empty();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::load_nurikabe;
+    use crate::nurikabe::tests::{data_puzzles, unsolvable_puzzle};
+    use crate::solvers::cdcl::{Cdcl, Lit, Outcome};
+
+    /// Solves a DIMACS file and prints the model like a SAT solver does.
+    ///
+    fn solve_dimacs(cnf: &str) -> String {
+        let mut cdcl = Cdcl::new();
+
+        for line in cnf.lines() {
+            if let Some(header) = line.strip_prefix("p cnf ") {
+                let num_vars = header.split_whitespace().next().unwrap();
+                for _ in 0..num_vars.parse::<usize>().unwrap() {
+                    cdcl.new_var();
+                }
+            } else if !line.starts_with('c') {
+                let clause = line
+                    .split_whitespace()
+                    .map(|token| token.parse::<i64>().unwrap())
+                    .take_while(|&lit| lit != 0)
+                    .map(|lit| Lit::new(lit.unsigned_abs() as usize - 1, lit > 0))
+                    .collect::<Vec<_>>();
+                cdcl.add_clause(&clause);
+            }
+        }
+
+        match cdcl.solve(usize::MAX) {
+            Outcome::Sat => {
+                let lits = cdcl
+                    .model()
+                    .iter()
+                    .enumerate()
+                    .map(|(var, &value)| {
+                        let lit = var as i64 + 1;
+                        (if value { lit } else { -lit }).to_string()
+                    })
+                    .collect::<Vec<_>>();
+                format!("s SATISFIABLE\nv {} 0\n", lits.join(" "))
+            }
+            _ => String::from("s UNSATISFIABLE\n"),
+        }
+    }
+
+    #[test]
+    fn dimacs_model_is_solution() {
+        for (name, puzzle) in data_puzzles() {
+            let (cnf, map) = to_dimacs(&puzzle);
+            let result = from_dimacs(&map, &solve_dimacs(&cnf)).unwrap();
+
+            assert!(result.solved, "{}", name);
+            assert_eq!(verify(&puzzle, &result), vec![], "{}", name);
+        }
+    }
+
+    #[test]
+    fn dimacs_unsolvable() {
+        let (cnf, map) = to_dimacs(&unsolvable_puzzle());
+
+        assert_eq!(
+            from_dimacs(&map, &solve_dimacs(&cnf)).unwrap_err(),
+            "Solver found no model"
+        );
+    }
+
+    #[test]
+    fn dimacs_var_map() {
+        let puzzle = load_nurikabe("0,2,0").unwrap();
+        let (_, map) = to_dimacs(&puzzle);
+
+        assert_eq!(map.cells, vec![1, 2, 3]);
+        assert_eq!(map.puzzle, vec![0, 2, 0]);
+        assert!(map
+            .members
+            .iter()
+            .all(|member| member.clue == (0, 1) && member.var > 3));
+
+        assert_eq!(
+            from_dimacs(&map, "v 1 -2 0").unwrap_err(),
+            "Model misses variable: 3"
+        );
+        assert_eq!(
+            from_dimacs(&map, "v 1 x 0").unwrap_err(),
+            "Invalid literal in model: x"
+        );
+    }
+
+    #[test]
+    fn smtlib_model() {
+        let puzzle = load_nurikabe("0,2,0").unwrap();
+        let smt = to_smtlib(&puzzle);
+
+        assert_eq!(smt.matches('(').count(), smt.matches(')').count());
+        assert!(smt.contains("(declare-const b_0_2 Bool)"));
+
+        let model = |left: bool, right: bool| {
+            format!(
+                "sat\n(\n  (define-fun b_0_0 () Bool {})\n  (define-fun b_0_1 () Bool false)\n  (define-fun b_0_2 () Bool {})\n)\n",
+                left, right
+            )
+        };
+
+        let result = from_smtlib(&puzzle, &model(true, false)).unwrap();
+        assert!(result.solved);
+        assert_eq!(result.data, vec![BLACK, 2, WHITE]);
+
+        // Island of three cells.
+        assert!(!from_smtlib(&puzzle, &model(false, false)).unwrap().solved);
+        assert_eq!(
+            from_smtlib(&puzzle, "unsat\n").unwrap_err(),
+            "Solver found no model"
+        );
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!("dimacs".parse::<Format>(), Ok(Format::Dimacs));
+        assert_eq!("smt2".parse::<Format>(), Ok(Format::SmtLib));
+        assert!("cnf".parse::<Format>().is_err());
+    }
+}
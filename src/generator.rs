@@ -0,0 +1,346 @@
+use std::collections::VecDeque;
+
+use crate::nurikabe::Nurikabe;
+use crate::solvers::{
+    difficulty::{rate, Tier},
+    for_valid_neighbours,
+    rng::Rng,
+    state::{BLACK, UNKNOWN},
+    unique::{uniqueness_within, Uniqueness},
+    NaiveSolver, Solver, Step,
+};
+
+/// Number of random solutions tried before the generator gives up.
+const MAX_ATTEMPTS: usize = 200;
+
+/// Number of times clues of one solution are moved, to make it unique.
+const MAX_ADJUSTMENTS: usize = 20;
+
+/// Backtracking steps allowed to check if a candidate puzzle is unique.
+/// Candidates that take longer are dropped.
+const MAX_SEARCH: usize = 500;
+
+/// Largest island in the solution of a puzzle of the tier.
+///
+fn max_island(tier: Tier) -> usize {
+    match tier {
+        Tier::Easy => 3,
+        Tier::Medium => 5,
+        Tier::Hard => 7,
+        Tier::Expert => 9,
+    }
+}
+
+/// Probability that a new white cell grows an existing island instead of
+/// starting a new one.
+///
+fn grow(tier: Tier) -> f64 {
+    match tier {
+        Tier::Easy => 0.3,
+        Tier::Medium => 0.6,
+        Tier::Hard => 0.75,
+        Tier::Expert => 0.85,
+    }
+}
+
+/// Generates a random puzzle with a unique solution, that `rate` puts in the
+/// given tier. The same seed always gives the same puzzle.
+///
+/// A random valid solution is built first (connected river, no pools), then
+/// one clue is placed in every island. Clues are moved within their islands
+/// until the puzzle has a single solution of the tier; if that doesn't work,
+/// a new solution is built.
+///
+pub fn generate(width: usize, height: usize, tier: Tier, seed: u64) -> Result<Nurikabe, String> {
+    if width < 2 || height < 2 {
+        return Err(format!(
+            "Puzzle must be at least 2 x 2, got {} x {}",
+            width, height
+        ));
+    }
+
+    let mut rng = Rng::new(seed);
+
+    for _ in 0..MAX_ATTEMPTS {
+        let Some(islands) = random_solution(width, height, tier, &mut rng) else {
+            continue;
+        };
+
+        let mut black = vec![true; width * height];
+        for &i in islands.iter().flatten() {
+            black[i] = false;
+        }
+
+        let mut clues = islands
+            .iter()
+            .map(|island| island[rng.random_int(0..island.len())])
+            .collect::<Vec<_>>();
+
+        for _ in 0..MAX_ADJUSTMENTS {
+            let mut data = vec![UNKNOWN; width * height];
+            for (island, &clue) in islands.iter().zip(clues.iter()) {
+                data[clue] = island.len() as i32;
+            }
+
+            let puzzle = Nurikabe::new(width, height, data);
+            if tier <= Tier::Medium && !is_rule_solvable(&puzzle) {
+                // Moving clues can still help, the rules depend on their position.
+                move_clues(&islands, &mut clues, &mut rng);
+                continue;
+            }
+
+            match uniqueness_within(puzzle.clone(), MAX_SEARCH) {
+                Some(Uniqueness::Unique(_)) => {
+                    if rate(puzzle.clone()).is_ok_and(|rating| rating.tier == tier) {
+                        return Ok(Nurikabe {
+                            seed: Some(seed),
+                            ..puzzle
+                        });
+                    }
+                    // Other clue positions need other techniques.
+                    move_clues(&islands, &mut clues, &mut rng);
+                }
+                Some(Uniqueness::Many(first, second)) => {
+                    // One of the two is the generated solution, rule out the other.
+                    let other = if is_black(&first) == black {
+                        second
+                    } else {
+                        first
+                    };
+                    if !exclude(&islands, &mut clues, &other, &mut rng) {
+                        move_clues(&islands, &mut clues, &mut rng);
+                    }
+                }
+                // Too slow to check, or no solution at all. Either way other
+                // clue positions are tried.
+                Some(Uniqueness::Unsolvable) | None => move_clues(&islands, &mut clues, &mut rng),
+            }
+        }
+    }
+
+    Err(format!(
+        "Couldn't generate a unique {} {} x {} puzzle in {} attempts",
+        tier, width, height, MAX_ATTEMPTS
+    ))
+}
+
+/// Writes the clues of the puzzle in the comma separated format that
+/// `load_nurikabe` reads. Cells without a clue are `0`.
+///
+pub fn to_csv(nurikabe: &Nurikabe) -> String {
+    nurikabe
+        .data
+        .chunks(nurikabe.width.max(1))
+        .map(|row| {
+            row.iter()
+                .map(|&v| v.max(0).to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// Builds a random valid solution and returns its islands as lists of cell
+/// indices. Starts with an all black grid and whitens cells of pools until
+/// none are left. Returns `None` when a pool can't be broken.
+///
+fn random_solution(
+    width: usize,
+    height: usize,
+    tier: Tier,
+    rng: &mut Rng,
+) -> Option<Vec<Vec<usize>>> {
+    let mut owner: Vec<Option<usize>> = vec![None; width * height];
+    let mut islands: Vec<Vec<usize>> = vec![];
+
+    while let Some(pool) = random_pool(width, height, &owner, rng) {
+        let mut candidates = vec![];
+
+        for &i in pool.iter() {
+            let mut touching = vec![];
+            for_valid_neighbours(width, height, i / width, i % width, |a, b| {
+                if let Some(o) = owner[a * width + b] {
+                    if !touching.contains(&o) {
+                        touching.push(o);
+                    }
+                }
+            });
+
+            match touching[..] {
+                [] => candidates.push((i, None)),
+                [o] if islands[o].len() < max_island(tier) => candidates.push((i, Some(o))),
+                _ => (),
+            }
+        }
+
+        // Growing keeps islands together, otherwise new islands are preferred.
+        let grow = rng.random_float() < grow(tier);
+        candidates.sort_by_key(|&(_, o)| o.is_some() != grow);
+
+        // Shuffle the preferred candidates among themselves.
+        let preferred = candidates
+            .iter()
+            .take_while(|&&(_, o)| o.is_some() == candidates[0].1.is_some())
+            .count();
+        for k in (1..preferred).rev() {
+            candidates.swap(k, rng.random_int(0..k + 1));
+        }
+
+        let choice = candidates
+            .into_iter()
+            .find(|&(i, _)| keeps_river(width, height, &owner, i))?;
+
+        match choice {
+            (i, Some(o)) => {
+                owner[i] = Some(o);
+                islands[o].push(i);
+            }
+            (i, None) => {
+                owner[i] = Some(islands.len());
+                islands.push(vec![i]);
+            }
+        }
+    }
+
+    if islands.is_empty() {
+        None
+    } else {
+        Some(islands)
+    }
+}
+
+/// Picks a random 2x2 block of black cells. Returns its cell indices.
+///
+fn random_pool(
+    width: usize,
+    height: usize,
+    owner: &[Option<usize>],
+    rng: &mut Rng,
+) -> Option<[usize; 4]> {
+    let mut pools = vec![];
+
+    for x in 0..height - 1 {
+        for y in 0..width - 1 {
+            let i = x * width + y;
+            let block = [i, i + 1, i + width, i + width + 1];
+            if block.iter().all(|&j| owner[j].is_none()) {
+                pools.push(block);
+            }
+        }
+    }
+
+    if pools.is_empty() {
+        None
+    } else {
+        Some(pools[rng.random_int(0..pools.len())])
+    }
+}
+
+/// Checks if black cells stay connected when the cell is whitened.
+///
+fn keeps_river(width: usize, height: usize, owner: &[Option<usize>], cell: usize) -> bool {
+    let is_black = |j: usize| j != cell && owner[j].is_none();
+    let Some(start) = (0..owner.len()).find(|&j| is_black(j)) else {
+        return false;
+    };
+
+    let mut reached = vec![false; owner.len()];
+    let mut queue = VecDeque::from([start]);
+    reached[start] = true;
+    let mut num = 0;
+
+    while let Some(i) = queue.pop_front() {
+        num += 1;
+        for_valid_neighbours(width, height, i / width, i % width, |a, b| {
+            let j = a * width + b;
+            if !reached[j] && is_black(j) {
+                reached[j] = true;
+                queue.push_back(j);
+            }
+        });
+    }
+
+    num == (0..owner.len()).filter(|&j| is_black(j)).count()
+}
+
+/// Moves every clue to a random cell of its island.
+///
+fn move_clues(islands: &[Vec<usize>], clues: &mut [usize], rng: &mut Rng) {
+    for (island, clue) in islands.iter().zip(clues.iter_mut()) {
+        *clue = island[rng.random_int(0..island.len())];
+    }
+}
+
+/// Rules out another solution of the puzzle, by moving one clue to a cell of
+/// its island that is black in the other solution. Clues can't be black, so
+/// the other solution stops being valid. Returns false if no clue can move.
+///
+fn exclude(islands: &[Vec<usize>], clues: &mut [usize], other: &Nurikabe, rng: &mut Rng) -> bool {
+    let options = islands
+        .iter()
+        .enumerate()
+        .flat_map(|(k, island)| island.iter().map(move |&i| (k, i)))
+        .filter(|&(_, i)| other.data[i] == BLACK)
+        .collect::<Vec<_>>();
+
+    if options.is_empty() {
+        return false;
+    }
+
+    let (k, i) = options[rng.random_int(0..options.len())];
+    clues[k] = i;
+    true
+}
+
+fn is_black(nurikabe: &Nurikabe) -> Vec<bool> {
+    nurikabe.data.iter().map(|&v| v == BLACK).collect()
+}
+
+/// Checks if the rules alone solve the puzzle, without guessing.
+///
+fn is_rule_solvable(puzzle: &Nurikabe) -> bool {
+    let mut solver = NaiveSolver::new(puzzle.clone());
+
+    loop {
+        match solver.solve() {
+            Step::Proceed => (),
+            step => {
+                return step == Step::SolutionFound && !solver.get_state().data.contains(&UNKNOWN)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solvers::unique::uniqueness;
+
+    #[test]
+    fn same_seed_same_puzzle() {
+        let puzzle = generate(5, 5, Tier::Easy, 7).unwrap();
+
+        assert_eq!(generate(5, 5, Tier::Easy, 7).unwrap(), puzzle);
+        assert_eq!(puzzle.seed, Some(7));
+    }
+
+    #[test]
+    fn unique_of_tier() {
+        for tier in [Tier::Easy, Tier::Medium, Tier::Hard, Tier::Expert] {
+            let puzzle = generate(5, 5, tier, 1).unwrap();
+            let unique = uniqueness(puzzle.clone());
+
+            assert!(matches!(unique, Uniqueness::Unique(_)), "{}", tier);
+            assert_eq!(rate(puzzle).unwrap().tier, tier, "{}", tier);
+        }
+    }
+
+    #[test]
+    fn too_small() {
+        assert_eq!(
+            generate(1, 5, Tier::Easy, 1).unwrap_err(),
+            "Puzzle must be at least 2 x 2, got 1 x 5"
+        );
+    }
+}
@@ -1,376 +1,354 @@
-use std::{cell::RefCell, rc::Rc};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
-use gloo_utils::format::JsValueSerdeExt;
-use nurikabe::{load_nurikabe, Nurikabe};
-// use rayon::iter::*;
+use nurikabe::Nurikabe;
 
 use serde::{Deserialize, Serialize};
-use solvers::{aco::AntSolver, random_ant::RandomAntSolver, NaiveSolver, Solver, Step};
-use wasm_bindgen::prelude::*;
-use web_sys::{HtmlElement, HtmlInputElement, MessageEvent};
-
+use solvers::{
+    aco::AntSolver, backtrack::BacktrackSolver, naive::Event, random_ant::RandomAntSolver, rng::Rng,
+    sat::SatSolver, NaiveSolver, Solver, Step,
+};
+use verify::verify;
+use web_time::{Duration, Instant};
+
+#[cfg(feature = "wasm")]
 pub mod log;
+pub mod export;
+pub mod generator;
 pub mod nurikabe;
 pub mod solvers;
 pub mod test;
+pub mod verify;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+#[cfg(feature = "wasm")]
+pub use wasm::*;
 
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
 pub struct Properties {
-    nurikabe: Nurikabe,
-    method: String,
-    ants: usize,
-    l_evap: f64,
-    g_evap: f64,
-    greedines: f64,
-	bve: f64,
-    max_iter: usize,
+    pub nurikabe: Nurikabe,
+    pub method: String,
+    pub ants: usize,
+    pub l_evap: f64,
+    pub g_evap: f64,
+    pub greedines: f64,
+	pub bve: f64,
+    pub max_iter: usize,
+    /// Seed for the ant solvers. A random seed is picked when not set.
+    pub seed: Option<u64>,
+    /// Wall-clock budget of the solve in ms, besides `max_iter`.
+    pub time_limit: Option<u64>,
 }
 
-#[wasm_bindgen]
-pub fn load(input: &str) -> Result<JsValue, String> {
-    let nurikabe: Nurikabe = load_nurikabe(input)?;
+/// Runs the solver selected with `properties.method` and returns its final
+/// state. Shared between the web worker and the native binary.
+///
+pub fn run_solver(properties: Properties) -> Result<Nurikabe, String> {
+    let mut session = Session::new(properties)?;
 
-    let result = serde_wasm_bindgen::to_value(&nurikabe).map_err(|error| format!("{}", error))?;
-    Ok(result)
-}
+    session.advance(usize::MAX);
 
-#[wasm_bindgen]
-pub struct NurikabeApp {
-    // previous: Option<Nurikabe>,
+    Ok(session.get_state())
 }
 
-#[wasm_bindgen]
-impl NurikabeApp {
-    pub fn new() -> Self {
-        Self { 
-			// previous: None
-		}
-    }
-
-    /// Do work in separate thread.
-    ///
-    pub fn start_solver(&mut self, properties: JsValue) -> Result<JsValue, String> {
-        let properties = JsValue::into_serde::<Properties>(&properties)
-            .map_err(|_| "Expects properties objects")?;
-
-        // self.previous = result
-        //     .clone()
-        //     .ok()
-        //     .map(|v| v.into_serde::<Nurikabe>().unwrap());
-
-		match &properties.method[..] {
-            "rules" => Ok(NurikabeApp::rule_solver(properties)),
-            "rand_ants" => Ok(NurikabeApp::random_ant(properties)),
-            "ants" => Ok(NurikabeApp::ant_colony_optimization(properties)),
-            method => Err(format!("Not implemented method: {}", &method)),
-        }
-    }
-
-    fn rule_solver(properties: Properties) -> JsValue {
-        let mut solver = NaiveSolver::new(properties.nurikabe);
-        solver.verbose = true;
-
-        let max_iter = 50;
-
-        while solver.get_iteration() < max_iter {
-            let step = solver.solve();
-
-            if step != Step::Proceed {
-                break;
-            }
-        }
-
-        solver.get_state()
-    }
-
-    fn random_ant(properties: Properties) -> JsValue {
-        let Properties {
-            nurikabe,
-            ants,
-            ..
-        } = properties;
-
-        let mut solver = RandomAntSolver::new(ants, nurikabe);
-        solver.verbose = true;
-
-        while solver.get_iteration() < properties.max_iter {
-            if solver.solve() != Step::Proceed {
-                break;
-            }
-        }
-
-		solver.get_state()
-
-    }
+/// Solver selected with `properties.method`, advanced a few iterations at a
+/// time so the caller can show the search in between. Limits are checked
+/// between iterations, a stopped session keeps its best state.
+///
+pub struct Session {
+    solver: Box<dyn Solver>,
+    max_iter: usize,
+    deadline: Option<Instant>,
+    /// Stops the session before its next iteration when set, from any thread.
+    pub cancel: Arc<AtomicBool>,
+    step: Step,
+}
 
-    fn ant_colony_optimization(properties: Properties) -> JsValue {
+impl Session {
+    pub fn new(properties: Properties) -> Result<Self, String> {
         let Properties {
             nurikabe,
             ants,
             l_evap,
             g_evap,
             greedines,
-			bve,
+            bve,
+            seed,
+            max_iter,
+            time_limit,
             ..
         } = properties;
 
-		let start_evap = 1.0 / (nurikabe.width * nurikabe.height) as f64;
-        let mut solver = AntSolver::new(ants, l_evap, g_evap, start_evap, greedines, bve, nurikabe);
-        solver.verbose = true;
-
-        while solver.get_iteration() < properties.max_iter {
-            let step = solver.solve();
+        let (solver, max_iter): (Box<dyn Solver>, usize) = match &properties.method[..] {
+            "rules" => {
+                let mut solver = NaiveSolver::new(nurikabe);
+                solver.verbose = true;
+                (Box::new(solver), RULES_MAX_ITER)
+            }
+            "rand_ants" => {
+                let seed = seed.unwrap_or_else(Rng::random_seed);
+                let mut solver = RandomAntSolver::new(ants, seed, nurikabe);
+                solver.verbose = true;
+                (Box::new(solver), max_iter)
+            }
+            "ants" => {
+                let start_evap = 1.0 / (nurikabe.width * nurikabe.height) as f64;
+                let seed = seed.unwrap_or_else(Rng::random_seed);
+                let mut solver =
+                    AntSolver::new(ants, l_evap, g_evap, start_evap, greedines, bve, seed, nurikabe);
+                solver.verbose = true;
+                (Box::new(solver), max_iter)
+            }
+            "backtrack" => {
+                let mut solver = BacktrackSolver::new(nurikabe);
+                solver.verbose = true;
+                (Box::new(solver), max_iter)
+            }
+            "sat" => {
+                let mut solver = SatSolver::new(nurikabe);
+                solver.verbose = true;
+                (Box::new(solver), max_iter)
+            }
+            method => return Err(format!("Not implemented method: {}", method)),
+        };
+
+        Ok(Self {
+            solver,
+            max_iter,
+            deadline: time_limit.map(|ms| Instant::now() + Duration::from_millis(ms)),
+            cancel: Arc::new(AtomicBool::new(false)),
+            step: Step::Proceed,
+        })
+    }
 
-            if step != Step::Proceed {
+    /// Runs at most `iterations` more iterations and returns the last step.
+    /// Stops early when the solver can't proceed, reaches `max_iter`, runs
+    /// out of time or is cancelled.
+    ///
+    pub fn advance(&mut self, iterations: usize) -> Step {
+        for _ in 0..iterations {
+            if self.is_finished() {
                 break;
             }
+
+            if self.cancel.load(Ordering::Relaxed) {
+                self.step = Step::Cancelled;
+            } else if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                self.step = Step::Timeout;
+            } else {
+                self.step = self.solver.solve();
+            }
         }
 
-		solver.get_state()
+        self.step
+    }
 
+    pub fn is_finished(&self) -> bool {
+        self.step != Step::Proceed || self.solver.get_iteration() >= self.max_iter
     }
-}
 
-impl Default for NurikabeApp {
-    fn default() -> Self {
-        Self::new()
+    /// Last step, `Proceed` until the solve is finished.
+    ///
+    pub fn get_step(&self) -> Step {
+        self.step
     }
-}
 
-#[wasm_bindgen]
-pub fn startup() {
-    set_panic_hook();
+    pub fn get_state(&self) -> Nurikabe {
+        self.solver.get_state()
+    }
 
-	console_log!("WASM Startup");
+    /// Snapshot to report while solving.
+    ///
+    pub fn get_progress(&self) -> Progress {
+        Progress {
+            state: self.solver.get_state(),
+            score: self.solver.get_score(),
+            step: self.step,
+            finished: self.is_finished(),
+        }
+    }
+}
 
-    // let worker_handle = Rc::new(RefCell::new(Worker::new("./worker.js").expect("Fuck")));
+/// State of a running solve, as posted by the web worker.
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Progress {
+    /// Best state found so far.
+    pub state: Nurikabe,
+    /// Score of the best state, see `Solver::get_score`.
+    pub score: Option<usize>,
+    pub step: Step,
+    pub finished: bool,
+}
 
-	let worker_options = web_sys::WorkerOptions::new();
-	worker_options.set_type(web_sys::WorkerType::Module);
-	let worker = web_sys::Worker::new_with_options("./worker.js", &worker_options).unwrap();
-    let worker_handle = Rc::new(RefCell::new(worker));
+/// Splits a solve into independent restarts, one per worker. Only the ant
+/// methods are random, so the other methods run once. The first restart keeps
+/// the seed of `properties`, the others draw theirs from it, so any result can
+/// be reproduced from the seed it reports. Drawn seeds are kept to 32 bits like
+/// `Rng::random_seed`, so they fit in a JS number.
+///
+pub fn split_restarts(properties: &Properties, workers: usize) -> Vec<Properties> {
+    if !matches!(&properties.method[..], "rand_ants" | "ants") {
+        return vec![properties.clone()];
+    }
 
-	console_log!("{:?}", &worker_handle);
+    let seed = properties.seed.unwrap_or_else(Rng::random_seed);
+    let mut rng = Rng::new(seed);
 
-    setup_callbacks(worker_handle);
+    (0..workers.max(1))
+        .map(|k| Properties {
+            seed: Some(if k == 0 { seed } else { rng.next_u64() >> 32 }),
+            ..properties.clone()
+        })
+        .collect()
 }
 
-#[wasm_bindgen]
-pub fn sum_of_squares(input: &[i32]) -> i32 {
-    input.iter() 
-         .map(|&i| i * i)
-         .sum()
-}
+/// Checks if `result` is better than `than`, both for the same puzzle. A
+/// solved grid wins, otherwise the one breaking fewer rules.
+///
+pub fn is_better(puzzle: &Nurikabe, result: &Nurikabe, than: &Nurikabe) -> bool {
+    if result.solved != than.solved {
+        return result.solved;
+    }
 
-#[wasm_bindgen]
-pub fn sum_of_squares_simple(input: &[i32]) -> i32 {
-    input.iter() 
-         .map(|&i| i * i)
-         .sum()
+    verify(puzzle, result).len() < verify(puzzle, than).len()
 }
 
-fn setup_callbacks(worker: Rc<RefCell<web_sys::Worker>>) {
-    let window = web_sys::window().unwrap();
-    let document = window.document().unwrap();
-
-    #[allow(unused_assignments)]
-    let mut persistent_callback_handle = get_on_msg_callback();
-
-    let on_click_callback = Closure::wrap(Box::new(move || {
-        let document = window.document().unwrap();
-
-		document
-            .get_element_by_id("progress")
-            .expect("#progress should exist")
-            .dyn_ref::<HtmlElement>()
-            .expect("#progress should be a HtmlInputElement")
-            .set_inner_text("In progress...");
-
-        let mut properties = Properties::default();
-
-        let get_element_by_id = document.get_element_by_id("ants");
-        properties.ants = get_element_by_id
-            .unwrap()
-            .dyn_ref::<HtmlInputElement>()
-            .unwrap()
-            .value()
-            .parse::<usize>()
-            .unwrap();
-
-        properties.max_iter = document
-            .get_element_by_id("max_iter")
-            .unwrap()
-            .dyn_ref::<HtmlInputElement>()
-            .unwrap()
-            .value()
-            .parse::<usize>()
-            .unwrap();
-
-        properties.l_evap = document
-            .get_element_by_id("local")
-            .unwrap()
-            .dyn_ref::<HtmlInputElement>()
-            .unwrap()
-            .value()
-            .parse::<f64>()
-            .unwrap();
-
-        properties.g_evap = document
-            .get_element_by_id("global")
-            .unwrap()
-            .dyn_ref::<HtmlInputElement>()
-            .unwrap()
-            .value()
-            .parse::<f64>()
-            .unwrap();
-
-        properties.greedines = document
-            .get_element_by_id("greedy")
-            .unwrap()
-            .dyn_ref::<HtmlInputElement>()
-            .unwrap()
-            .value()
-            .parse::<f64>()
-            .unwrap();
-
-        properties.bve = document
-            .get_element_by_id("bve")
-            .unwrap()
-            .dyn_ref::<HtmlInputElement>()
-            .unwrap()
-            .value()
-            .parse::<f64>()
-            .unwrap();
-
-
-        let nurikabe = window.get("nurikabe").unwrap();
-        let method = window.get("method").unwrap();
-        properties.nurikabe = JsValue::into_serde::<Nurikabe>(&nurikabe).expect("Nurikabe!");
-        properties.method = JsValue::into_serde::<String>(&method).expect("Method!");
-
-        let worker_handle = &*worker.borrow();
-        let _ = worker_handle.post_message(&serde_wasm_bindgen::to_value(&properties).unwrap());
-        persistent_callback_handle = get_on_msg_callback();
-
-        // Since the worker returns the message asynchronously, we attach a callback to be
-        // triggered when the worker returns.
-        worker_handle.set_onmessage(Some(persistent_callback_handle.as_ref().unchecked_ref()));
-    }) as Box<dyn FnMut()>);
-
-    document
-        .get_element_by_id("solve")
-        .expect("#solve should exist")
-        .dyn_ref::<HtmlElement>()
-        .expect("#solve should be a HtmlElement")
-        .set_onclick(Some(on_click_callback.as_ref().unchecked_ref()));
-
-    on_click_callback.forget();
+/// Every iteration of the rule solver, as returned by `rule_trace`.
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trace {
+    /// Final state of the solver.
+    pub state: Nurikabe,
+    pub events: Vec<Event>,
 }
 
-/// Create a closure to act on the message returned by the worker
+/// Runs the rule solver like the `rules` method and records each iteration,
+/// so the solve can be replayed or compared between versions.
 ///
-fn get_on_msg_callback() -> Closure<dyn FnMut(MessageEvent)> {
-    Closure::wrap(Box::new(move |event: MessageEvent| {
-        let solver_result: Nurikabe = event.data().into_serde().expect("Nurikabe Result.");
-
-		let window = web_sys::window().unwrap();
-		let document = window.document().unwrap();
-		document
-			.get_element_by_id("progress")
-			.expect("#progress should exist")
-			.dyn_ref::<HtmlElement>()
-			.expect("#progress should be a HtmlInputElement")
-			.set_inner_text("");
-
-        view_nurikabe(solver_result);
-    }) as Box<dyn FnMut(_)>)
-}
+pub fn rule_trace(nurikabe: Nurikabe) -> Trace {
+    let mut solver = NaiveSolver::new(nurikabe);
+    solver.trace = true;
 
-/// Update nurikabe grid.
-fn view_nurikabe(nurikabe: Nurikabe) {
-    // let previous = window
-    //     .get("previous")
-    //     .map(|p| JsValue::into_serde::<Nurikabe>(&p).unwrap());
+    run_rules(&mut solver);
 
-    let window = web_sys::window().unwrap();
-    let document = window.document().unwrap();
+    Trace {
+        state: solver.get_state(),
+        events: solver.get_trace().to_vec(),
+    }
+}
 
-	let parent = document.get_element_by_id("nurikabe").unwrap();
-	parent.set_inner_html("");
+/// Iteration limit of the rule solver, which doesn't use `max_iter`.
+///
+const RULES_MAX_ITER: usize = 50;
 
-    let width = nurikabe.width;
-    let height = nurikabe.height;
+fn run_rules(solver: &mut NaiveSolver) {
+    solver.verbose = true;
 
-    let grid_parent = document.create_element("div").unwrap();
+    while solver.get_iteration() < RULES_MAX_ITER {
+        let step = solver.solve();
 
-    if nurikabe.iteration > 0 {
-        let step_text = document.create_element("p").unwrap();
-        step_text.set_inner_html(&format!("{}. {}", nurikabe.iteration, nurikabe.verbose));
-        let _ = grid_parent.append_child(&step_text);
+        if step != Step::Proceed {
+            break;
+        }
     }
+}
 
-    let grid = document.create_element("div").unwrap();
-    grid.set_class_name("grid");
-    grid.set_id("grid");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::tests::data_puzzle;
+
+    fn ants(max_iter: usize, time_limit: Option<u64>) -> Properties {
+        Properties {
+            nurikabe: data_puzzle("nurikabe10x10v1.csv"),
+            method: String::from("ants"),
+            ants: 10,
+            l_evap: 0.1,
+            g_evap: 0.2,
+            greedines: 0.9,
+            bve: 0.001,
+            max_iter,
+            seed: Some(1),
+            time_limit,
+        }
+    }
 
-    for i in 0..height {
-        let row = document.create_element("div").unwrap();
-        row.set_class_name("row");
+    #[test]
+    fn session_cancel_keeps_best_state() {
+        let mut session = Session::new(ants(1000, None)).unwrap();
+        assert_eq!(session.advance(5), Step::Proceed);
+        let best = session.get_state();
 
-        for j in 0..width {
-            let value = nurikabe.data[i * width + j];
-            // let previous_value = match &previous {
-            //     Some(p) => p.data[i * width + j],
-            //     None => value,
-            // };
+        session.cancel.store(true, Ordering::Relaxed);
+        assert_eq!(session.advance(5), Step::Cancelled);
+        assert_eq!(session.get_state(), best);
 
-            let cell = document.create_element("div").unwrap();
+        let progress = session.get_progress();
+        assert!(progress.finished);
+        assert_eq!(progress.step, Step::Cancelled);
+    }
 
-            if value > 0 {
-                cell.set_inner_html(&format!("{}", value));
-            } else {
-                cell.set_inner_html(" ");
-            }
+    #[test]
+    fn session_timeout() {
+        let mut session = Session::new(ants(1000, Some(0))).unwrap();
+        assert_eq!(session.advance(5), Step::Timeout);
+        assert!(session.is_finished());
+        assert_eq!(session.get_state().iteration, 0);
+    }
 
-            if false {
-                // window.get("previous_coloring").unwrap().as_bool().unwrap()
-                // && previous_value != value
-                if value == -1 {
-                    cell.set_class_name("new_black");
-                } else {
-                    cell.set_class_name("new");
-                }
-            } else {
-                match value {
-                    -3 => cell.set_class_name("unknown"),
-                    -2 => cell.set_class_name("white"),
-                    -1 => cell.set_class_name("black"),
-                    _ => cell.set_class_name("white"),
-                }
-            }
-            let _ = row.append_child(&cell);
-        }
+    #[test]
+    fn session_stops_at_max_iter() {
+        // Every ant of the colony counts as an iteration.
+        let mut session = Session::new(ants(30, None)).unwrap();
+        assert_eq!(session.advance(2), Step::Proceed);
+        assert!(!session.is_finished());
+        assert_eq!(session.get_state().iteration, 20);
+
+        assert_eq!(session.advance(100), Step::Proceed);
+        assert!(session.is_finished());
+        assert_eq!(session.get_state().iteration, 30);
+
+        // Nothing left to run.
+        session.advance(100);
+        assert_eq!(session.get_state().iteration, 30);
+    }
 
-        let _ = grid.append_child(&row);
+    #[test]
+    fn session_unknown_method() {
+        let properties = Properties {
+            method: String::from("magic"),
+            ..ants(3, None)
+        };
+        assert_eq!(
+            Session::new(properties).err().unwrap(),
+            "Not implemented method: magic"
+        );
     }
 
-    let _ = grid_parent.append_child(&grid);
-    let _ = parent.append_child(&grid_parent);
-
-    // Update properties
-    let properties = document.get_element_by_id("properties").unwrap();
-    properties.set_inner_html(&format!(
-        "<i>File:</i> {} <br/>
-		<i>Dims:</i> {} x {} <br/>
-		<i>Solved:</i> <b>{}</b> <br/>
-		<i>Iteration:</i> <b>{}</b> <br/>
-		<i>Time:</i> {} ms<br/>",
-        nurikabe.path, nurikabe.width, nurikabe.height, nurikabe.solved, nurikabe.iteration, nurikabe.duration
-    ));
-    // todo, add to window.
-}
+    #[test]
+    fn split_restarts_seeds() {
+        let properties = Properties {
+            method: String::from("ants"),
+            seed: Some(u64::MAX),
+            ..Properties::default()
+        };
+
+        let restarts = split_restarts(&properties, 8);
+        assert_eq!(restarts.len(), 8);
+        assert_eq!(restarts[0].seed, Some(u64::MAX));
+        for restart in &restarts[1..] {
+            // Has to fit in a JS number to reach the workers.
+            assert!(restart.seed.unwrap() <= 1 << 53);
+        }
 
-pub fn set_panic_hook() {
-    #[cfg(feature = "console_error_panic_hook")]
-    console_error_panic_hook::set_once();
+        let properties = Properties {
+            method: String::from("rules"),
+            ..properties
+        };
+        assert_eq!(split_restarts(&properties, 8).len(), 1);
+    }
 }
@@ -0,0 +1,469 @@
+use std::{
+    env, fs,
+    process::{self, ExitCode},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use nurikabe::{
+    export::{from_dimacs, from_smtlib, to_dimacs, to_smtlib, Format},
+    generator::{generate, to_csv},
+    nurikabe::{is_url, load_url, parse_nurikabe, to_pzprv3, to_url, Nurikabe},
+    rule_trace,
+    solvers::{
+        difficulty::{rate, Tier},
+        rng::Rng,
+        unique::{uniqueness, Uniqueness},
+        Step,
+    },
+    verify::verify,
+    Properties, Session,
+};
+
+const USAGE: &str = "Usage: nurikabe [OPTIONS] <FILE>...
+       nurikabe --generate <WIDTH>x<HEIGHT> [--difficulty <LEVEL>] [--seed <N>]
+
+Solves nurikabe puzzles stored as comma separated values or in the pzprv3 format
+of pzprjs. A puzz.link URL can be given instead of a file.
+
+Options:
+  -m, --method <METHOD>  Solver to use: rules, rand_ants, ants, backtrack or sat [default: ants]
+      --ants <N>         Number of ants per iteration [default: 10]
+      --max-iter <N>     Maximum number of iterations [default: 5000]
+      --l-evap <F>       Local evaporation [default: 0.1]
+      --g-evap <F>       Global evaporation [default: 0.2]
+      --greedines <F>    Greedines of ants, between 0.0 and 1.0 [default: 0.9]
+      --bve <F>          Best value evaporation [default: 0.001]
+      --seed <N>         Seed for the ant solvers and the generator [default: random]
+      --time-limit <MS>  Stop each solve after this many milliseconds [default: none]
+      --unique           Check if puzzles have exactly one solution instead of solving
+      --rate             Rate the difficulty of puzzles instead of solving
+      --trace            Print every iteration of the rule solver as JSON
+      --url              Print the puzz.link URL of puzzles instead of solving
+      --pzprv3           Print puzzles in the pzprv3 format instead of solving
+      --export <FORMAT>  Write puzzles for external solvers next to them: dimacs (.cnf and
+                         .map.json) or smt2 (.smt2)
+      --import <MODEL>   Read the model of an external solver for the puzzle and check it
+      --generate <WxH>   Print a new puzzle with a unique solution as CSV
+      --difficulty <L>   Tier of the generated puzzle: easy, medium, hard or expert [default: medium]
+  -h, --help             Print this help";
+
+struct Args {
+    properties: Properties,
+    unique: bool,
+    rate: bool,
+    trace: bool,
+    url: bool,
+    pzprv3: bool,
+    export: Option<Format>,
+    import: Option<String>,
+    generate: Option<(usize, usize)>,
+    difficulty: Tier,
+    files: Vec<String>,
+}
+
+/// Same defaults as the web page uses (see `index.js`).
+///
+fn default_properties() -> Properties {
+    Properties {
+        method: String::from("ants"),
+        ants: 10,
+        max_iter: 5000,
+        l_evap: 0.1,
+        g_evap: 0.2,
+        greedines: 0.9,
+        bve: 0.001,
+        ..Default::default()
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("Missing value for {}", option))?;
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid value for {}: {}", option, value))
+}
+
+/// Parses puzzle size given as `<WIDTH>x<HEIGHT>`.
+///
+fn parse_size(option: &str, value: Option<String>) -> Result<(usize, usize), String> {
+    let value = value.ok_or_else(|| format!("Missing value for {}", option))?;
+    let invalid = || format!("Invalid value for {}: {}", option, value);
+
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let width = width.parse().map_err(|_| invalid())?;
+    let height = height.parse().map_err(|_| invalid())?;
+
+    Ok((width, height))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut properties = default_properties();
+    let mut unique = false;
+    let mut rate = false;
+    let mut trace = false;
+    let mut url = false;
+    let mut pzprv3 = false;
+    let mut export = None;
+    let mut import = None;
+    let mut generate = None;
+    let mut difficulty = Tier::Medium;
+    let mut files = vec![];
+
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "-h" | "--help" => return Ok(None),
+            "-m" | "--method" => properties.method = parse_value(&arg, args.next())?,
+            "--ants" => properties.ants = parse_value(&arg, args.next())?,
+            "--max-iter" => properties.max_iter = parse_value(&arg, args.next())?,
+            "--l-evap" => properties.l_evap = parse_value(&arg, args.next())?,
+            "--g-evap" => properties.g_evap = parse_value(&arg, args.next())?,
+            "--greedines" => properties.greedines = parse_value(&arg, args.next())?,
+            "--bve" => properties.bve = parse_value(&arg, args.next())?,
+            "--seed" => properties.seed = Some(parse_value(&arg, args.next())?),
+            "--time-limit" => properties.time_limit = Some(parse_value(&arg, args.next())?),
+            "--unique" => unique = true,
+            "--rate" => rate = true,
+            "--trace" => trace = true,
+            "--url" => url = true,
+            "--pzprv3" => pzprv3 = true,
+            "--export" => export = Some(parse_value(&arg, args.next())?),
+            "--import" => import = Some(parse_value(&arg, args.next())?),
+            "--generate" => generate = Some(parse_size(&arg, args.next())?),
+            "--difficulty" => difficulty = parse_value(&arg, args.next())?,
+            option if option.starts_with('-') => {
+                return Err(format!("Unknown option: {}", option))
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    if files.is_empty() && generate.is_none() {
+        return Err(String::from("Expects at least one puzzle file"));
+    }
+
+    Ok(Some(Args {
+        properties,
+        unique,
+        rate,
+        trace,
+        url,
+        pzprv3,
+        export,
+        import,
+        generate,
+        difficulty,
+        files,
+    }))
+}
+
+/// Reads the puzzle of a file in any format, or of the link when `path` is a
+/// puzz.link URL.
+/// The puzzle keeps `path` as its path.
+///
+fn read_puzzle(path: &str) -> Result<Nurikabe, String> {
+    let mut nurikabe = if is_url(path) {
+        load_url(path)?
+    } else {
+        let input = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+        parse_nurikabe(&input)?
+    };
+    nurikabe.path = String::from(path);
+
+    Ok(nurikabe)
+}
+
+/// Loads and solves a single puzzle. Returns if the puzzle was solved. The
+/// solve stops with its best state when `cancel` is set.
+///
+fn solve_file(
+    path: &str,
+    properties: &Properties,
+    cancel: &Arc<AtomicBool>,
+) -> Result<bool, String> {
+    let nurikabe = read_puzzle(path)?;
+
+    let start_time = Instant::now();
+    let mut session = Session::new(Properties {
+        nurikabe,
+        ..properties.clone()
+    })?;
+    session.cancel = cancel.clone();
+    session.advance(usize::MAX);
+
+    let mut result = session.get_state();
+    result.duration = start_time.elapsed().as_millis() as usize;
+
+    println!("File: {}", path);
+    print!("{}", result);
+    if !result.verbose.is_empty() {
+        println!("{}. {}", result.iteration, result.verbose);
+    }
+    println!("Solved: {}", result.solved);
+    match session.get_step() {
+        Step::Timeout => println!("Stopped: time limit"),
+        Step::Cancelled => println!("Stopped: cancelled"),
+        _ => {}
+    }
+    println!("Iteration: {}", result.iteration);
+    if let Some(seed) = result.seed {
+        println!("Seed: {}", seed);
+    }
+    println!("Time: {} ms", result.duration);
+
+    Ok(result.solved)
+}
+
+/// Counts solutions of a single puzzle. Returns if the solution is unique.
+///
+fn check_file(path: &str) -> Result<bool, String> {
+    let nurikabe = read_puzzle(path)?;
+
+    let start_time = Instant::now();
+    let result = uniqueness(nurikabe);
+    let duration = start_time.elapsed().as_millis();
+
+    println!("File: {}", path);
+    let unique = match result {
+        Uniqueness::Unsolvable => {
+            println!("Solutions: 0");
+            false
+        }
+        Uniqueness::Unique(solution) => {
+            print!("{}", solution);
+            println!("Solutions: 1");
+            true
+        }
+        Uniqueness::Many(first, second) => {
+            print!("{}\n{}", first, second);
+            println!("Solutions: many");
+            false
+        }
+    };
+    println!("Time: {} ms", duration);
+
+    Ok(unique)
+}
+
+/// Rates the difficulty of a single puzzle. Returns if the puzzle could be
+/// solved.
+///
+fn rate_file(path: &str) -> Result<bool, String> {
+    let nurikabe = read_puzzle(path)?;
+
+    println!("File: {}", path);
+    let solved = match rate(nurikabe) {
+        Ok(rating) => {
+            for (rule, num) in rating.rules.iter() {
+                println!("{:?}: {}", rule, num);
+            }
+            println!("Probes: {}", rating.probes);
+            println!("Guesses: {}", rating.guesses);
+            println!("Score: {}", rating.score);
+            println!("Tier: {}", rating.tier);
+            true
+        }
+        Err(error) => {
+            println!("{}", error);
+            false
+        }
+    };
+
+    Ok(solved)
+}
+
+/// Prints the rule solver trace of a single puzzle as JSON. Returns if the
+/// puzzle was solved.
+///
+fn trace_file(path: &str) -> Result<bool, String> {
+    let nurikabe = read_puzzle(path)?;
+
+    let trace = rule_trace(nurikabe);
+    let json = serde_json::to_string_pretty(&trace).map_err(|error| format!("{}", error))?;
+    println!("{}", json);
+
+    Ok(trace.state.solved)
+}
+
+/// Prints the puzz.link URL of a single puzzle.
+///
+fn url_file(path: &str) -> Result<bool, String> {
+    let nurikabe = read_puzzle(path)?;
+
+    println!("File: {}", path);
+    println!("{}", to_url(&nurikabe));
+
+    Ok(true)
+}
+
+/// Prints a single puzzle in the pzprv3 format.
+///
+fn pzprv3_file(path: &str) -> Result<bool, String> {
+    let nurikabe = read_puzzle(path)?;
+
+    print!("{}", to_pzprv3(&nurikabe));
+
+    Ok(true)
+}
+
+/// Writes the puzzle for external solvers, next to the puzzle file. Puzzles
+/// of a link are written to the working directory.
+///
+fn export_file(path: &str, format: Format) -> Result<bool, String> {
+    let nurikabe = read_puzzle(path)?;
+
+    let stem = if is_url(path) {
+        format!("nurikabe_{}x{}", nurikabe.width, nurikabe.height)
+    } else {
+        String::from(path.strip_suffix(".csv").unwrap_or(path))
+    };
+    let write = |path: String, content: String| {
+        fs::write(&path, content).map_err(|error| format!("{}: {}", path, error))?;
+        println!("Wrote: {}", path);
+        Ok::<(), String>(())
+    };
+
+    println!("File: {}", path);
+    match format {
+        Format::Dimacs => {
+            let (cnf, map) = to_dimacs(&nurikabe);
+            let map = serde_json::to_string_pretty(&map).map_err(|error| format!("{}", error))?;
+
+            write(format!("{}.cnf", stem), cnf)?;
+            write(format!("{}.map.json", stem), map)?;
+        }
+        Format::SmtLib => write(format!("{}.smt2", stem), to_smtlib(&nurikabe))?,
+    }
+
+    Ok(true)
+}
+
+/// Reads the model of an external solver for the puzzle, SMT-LIB when it
+/// defines constants and DIMACS otherwise. Returns if the model is a
+/// solution.
+///
+fn import_file(path: &str, model_path: &str) -> Result<bool, String> {
+    let nurikabe = read_puzzle(path)?;
+    let model =
+        fs::read_to_string(model_path).map_err(|error| format!("{}: {}", model_path, error))?;
+
+    let smt = model.contains("define-fun") || model.trim_start().starts_with("unsat");
+    let result = if smt {
+        from_smtlib(&nurikabe, &model)
+    } else {
+        from_dimacs(&to_dimacs(&nurikabe).1, &model)
+    };
+
+    println!("File: {}", path);
+    match result {
+        Ok(result) => {
+            print!("{}", result);
+            for violation in verify(&nurikabe, &result) {
+                println!("{}", violation);
+            }
+            println!("Solved: {}", result.solved);
+
+            Ok(result.solved)
+        }
+        Err(error) => {
+            println!("{}", error);
+            Ok(false)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    if let Some((width, height)) = args.generate {
+        let seed = args.properties.seed.unwrap_or_else(Rng::random_seed);
+
+        return match generate(width, height, args.difficulty, seed) {
+            Ok(puzzle) => {
+                print!("{}", to_csv(&puzzle));
+                eprintln!("Seed: {}", seed);
+                ExitCode::SUCCESS
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                ExitCode::from(2)
+            }
+        };
+    }
+
+    // Ctrl-C stops the running solve, which still prints its best state, and a
+    // second one quits right away. The other modes keep the default handler.
+    let solving = !(args.unique || args.rate || args.trace || args.url || args.pzprv3)
+        && args.export.is_none()
+        && args.import.is_none();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let handler_cancel = cancel.clone();
+    if solving {
+        let handler = move || {
+            if handler_cancel.swap(true, Ordering::Relaxed) {
+                process::exit(130);
+            }
+        };
+        if let Err(error) = ctrlc::set_handler(handler) {
+            eprintln!("{}", error);
+        }
+    }
+
+    let mut all_solved = true;
+
+    for (i, path) in args.files.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let result = if args.unique {
+            check_file(path)
+        } else if args.rate {
+            rate_file(path)
+        } else if args.trace {
+            trace_file(path)
+        } else if args.url {
+            url_file(path)
+        } else if args.pzprv3 {
+            pzprv3_file(path)
+        } else if let Some(format) = args.export {
+            export_file(path, format)
+        } else if let Some(model) = &args.import {
+            import_file(path, model)
+        } else {
+            solve_file(path, &args.properties, &cancel)
+        };
+
+        match result {
+            Ok(solved) => all_solved &= solved,
+            Err(error) => {
+                eprintln!("{}", error);
+                return ExitCode::from(2);
+            }
+        }
+
+        if cancel.load(Ordering::Relaxed) {
+            all_solved = false;
+            break;
+        }
+    }
+
+    if all_solved {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
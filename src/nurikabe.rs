@@ -1,6 +1,10 @@
+use std::fmt;
+
 use serde::*;
 
-#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+use crate::solvers::state::{State, BLACK, UNKNOWN, WHITE};
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Debug)]
 pub struct Nurikabe {
 	pub path: String,
     pub width: usize,
@@ -9,6 +13,8 @@ pub struct Nurikabe {
     pub iteration: usize,
     pub data: Vec<i32>,
 	pub duration: usize,
+    /// Seed of the random generator used by the solver, if any.
+    pub seed: Option<u64>,
     pub verbose: String,
 }
 
@@ -22,38 +28,740 @@ impl Nurikabe {
             iteration: 0,
             data,
 			duration: 0,
+            seed: None,
             verbose: String::from(""),
         }
     }
 }
 
-pub fn load_nurikabe(input: &str) -> Result<Nurikabe, String> {
-    let mut width: usize = 0;
-    let mut height: usize = 0;
+/// Text view of the grid, one row per line. Black cells are `#`, white cells
+/// `.`, unknown cells `?` and islands show their clue.
+///
+impl fmt::Display for Nurikabe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.data.chunks(self.width.max(1)) {
+            let line = row
+                .iter()
+                .map(|&value| match value {
+                    -1 => String::from("  #"),
+                    -2 => String::from("  ."),
+                    1.. => format!("{:>3}", value),
+                    _ => String::from("  ?"),
+                })
+                .collect::<String>();
+
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Why a puzzle couldn't be loaded. Lines and columns count from 1, a column
+/// is the position of the value in its row. For URLs the line is the row of
+/// the board.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParseError {
+    /// Input has no rows.
+    Empty,
+    /// Value isn't a number.
+    InvalidValue {
+        line: usize,
+        column: usize,
+        value: String,
+    },
+    /// Negative value, clues are positive and other cells `0`.
+    Negative {
+        line: usize,
+        column: usize,
+        value: i32,
+    },
+    /// Row has a different number of values than the first row, or than the
+    /// header says.
+    Ragged {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// Clue is larger than the whole board.
+    ClueTooLarge {
+        line: usize,
+        column: usize,
+        clue: i32,
+        area: usize,
+    },
+    /// Islands of the clues need more cells than the board has.
+    CluesExceedArea { sum: usize, area: usize },
+    /// Link isn't a puzz.link URL of a nurikabe.
+    InvalidUrl(String),
+    /// File isn't a pzprv3 file of a nurikabe.
+    InvalidPzprv3 { line: usize, reason: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Puzzle is empty"),
+            ParseError::InvalidValue {
+                line,
+                column,
+                value,
+            } => write!(
+                f,
+                "Line {}, column {}: '{}' is not a number",
+                line, column, value
+            ),
+            ParseError::Negative {
+                line,
+                column,
+                value,
+            } => write!(f, "Line {}, column {}: {} is negative", line, column, value),
+            ParseError::Ragged {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Line {}: row has {} values instead of {}",
+                line, found, expected
+            ),
+            ParseError::ClueTooLarge {
+                line,
+                column,
+                clue,
+                area,
+            } => write!(
+                f,
+                "Line {}, column {}: clue {} is larger than the board of {} cells",
+                line, column, clue, area
+            ),
+            ParseError::CluesExceedArea { sum, area } => write!(
+                f,
+                "Clues need {} cells, but the board has only {}",
+                sum, area
+            ),
+            ParseError::InvalidUrl(reason) => write!(f, "Invalid puzz.link URL: {}", reason),
+            ParseError::InvalidPzprv3 { line, reason } => write!(f, "Line {}: {}", line, reason),
+        }
+    }
+}
+
+impl From<ParseError> for String {
+    fn from(error: ParseError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Reads a puzzle in any of the supported formats, picked from the content:
+/// a puzz.link URL, a pzprv3 file or comma separated values.
+///
+pub fn parse_nurikabe(input: &str) -> Result<Nurikabe, ParseError> {
+    if is_url(input) {
+        load_url(input)
+    } else if is_pzprv3(input) {
+        load_pzprv3(input)
+    } else {
+        load_nurikabe(input)
+    }
+}
+
+/// Reads a puzzle from comma separated values, one row per line. Clues are
+/// positive and other cells `0`. Blank lines are skipped.
+///
+pub fn load_nurikabe(input: &str) -> Result<Nurikabe, ParseError> {
+    let mut rows = vec![];
+
+    for (k, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let row = line
+            .split(',')
+            .enumerate()
+            .map(|(column, value)| parse_clue(k + 1, column + 1, value.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.push((k + 1, row));
+    }
+
+    from_rows(rows)
+}
+
+/// Clue or `0` at the given line and column.
+///
+fn parse_clue(line: usize, column: usize, value: &str) -> Result<i32, ParseError> {
+    match value.parse::<i32>() {
+        Ok(v) if v < 0 => Err(ParseError::Negative {
+            line,
+            column,
+            value: v,
+        }),
+        Ok(v) => Ok(v),
+        Err(_) => Err(ParseError::InvalidValue {
+            line,
+            column,
+            value: String::from(value),
+        }),
+    }
+}
+
+/// Checks rows of clues and `0`, each with its line, and builds the puzzle.
+///
+fn from_rows(rows: Vec<(usize, Vec<i32>)>) -> Result<Nurikabe, ParseError> {
+    let width = rows.first().ok_or(ParseError::Empty)?.1.len();
+    let height = rows.len();
+    let area = width * height;
+
+    for (line, row) in rows.iter() {
+        if row.len() != width {
+            return Err(ParseError::Ragged {
+                line: *line,
+                expected: width,
+                found: row.len(),
+            });
+        }
+
+        for (column, &clue) in row.iter().enumerate() {
+            if clue as usize > area {
+                return Err(ParseError::ClueTooLarge {
+                    line: *line,
+                    column: column + 1,
+                    clue,
+                    area,
+                });
+            }
+        }
+    }
+
+    let data = rows
+        .into_iter()
+        .flat_map(|(_, row)| row)
+        .map(|v| if v == 0 { UNKNOWN } else { v })
+        .collect::<Vec<_>>();
+
+    let sum = data.iter().filter(|&&v| v > 0).map(|&v| v as usize).sum();
+    if sum > area {
+        return Err(ParseError::CluesExceedArea { sum, area });
+    }
+
+    Ok(Nurikabe::new(width, height, data))
+}
+
+/// Tells if the input is a link rather than a file, `load_url` reads it.
+/// Links start with `http://` or `https://` or have a `?nurikabe/` query, so
+/// paths of files in a `nurikabe` directory aren't taken for links.
+///
+pub fn is_url(input: &str) -> bool {
+    let input = input.trim();
+    let link = input.starts_with("http://")
+        || input.starts_with("https://")
+        || input.contains("?nurikabe/");
+
+    link && !input.contains(char::is_whitespace)
+}
+
+/// Reads a puzzle from a puzz.link (or pzv.jp) URL such as
+/// `https://puzz.link/p?nurikabe/5/5/g1h3...`. The part after `?` may also be
+/// given on its own.
+///
+pub fn load_url(url: &str) -> Result<Nurikabe, ParseError> {
+    let invalid = |reason: &str| ParseError::InvalidUrl(String::from(reason));
+
+    let url = url.trim();
+    let query = url.split_once('?').map_or(url, |(_, query)| query);
+    let mut parts = query.split('/');
+
+    if parts.next() != Some("nurikabe") {
+        return Err(invalid("not a nurikabe"));
+    }
+
+    // Flags like `v:` may come before the size, none matter for nurikabe.
+    let mut parts = parts.skip_while(|part| part.parse::<usize>().is_err());
+    let width = parts.next().and_then(|v| v.parse::<usize>().ok());
+    let height = parts.next().and_then(|v| v.parse::<usize>().ok());
+    let (width, height) = match (width, height) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => (width, height),
+        _ => return Err(invalid("missing board size")),
+    };
+    let body = parts.next().unwrap_or("").as_bytes();
+
+    let area = width * height;
+    let mut data = vec![0; area];
+    let mut cell = 0;
+    let mut k = 0;
 
-    const UNKNOWN: i32 = -3;
+    // Number encoding of pzprjs: a hex digit for clues below 16, `-` with two,
+    // `+` with three digits and `=`, `%` with three digits above 4096 and
+    // 8192. Letters `g` to `z` skip 1 to 20 cells.
+    while cell < area && k < body.len() {
+        let hex = |start: usize, len: usize| {
+            body.get(start..start + len)
+                .and_then(|digits| std::str::from_utf8(digits).ok())
+                .and_then(|digits| i32::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| invalid("truncated number"))
+        };
 
-    let input = input
+        match body[k] {
+            c @ (b'0'..=b'9' | b'a'..=b'f') => {
+                data[cell] = (c as char).to_digit(16).unwrap_or(0) as i32;
+                k += 1;
+            }
+            b'-' => {
+                data[cell] = hex(k + 1, 2)?;
+                k += 3;
+            }
+            b'+' => {
+                data[cell] = hex(k + 1, 3)?;
+                k += 4;
+            }
+            b'=' => {
+                data[cell] = hex(k + 1, 3)? + 4096;
+                k += 4;
+            }
+            b'%' => {
+                data[cell] = hex(k + 1, 3)? + 8192;
+                k += 4;
+            }
+            b'.' => return Err(invalid("clues with a question mark are not supported")),
+            c @ b'g'..=b'z' => {
+                cell += (c - b'g') as usize;
+                k += 1;
+            }
+            c => {
+                return Err(ParseError::InvalidUrl(format!(
+                    "unexpected '{}'",
+                    c as char
+                )))
+            }
+        }
+
+        cell += 1;
+    }
+
+    let rows = data
+        .chunks(width)
+        .enumerate()
+        .map(|(row, values)| (row + 1, values.to_vec()))
+        .collect();
+
+    from_rows(rows)
+}
+
+/// Link to the puzzle on puzz.link, with the clues only. Clues above 12287
+/// have no encoding and are left out.
+///
+pub fn to_url(nurikabe: &Nurikabe) -> String {
+    let mut body = String::new();
+    let mut skipped = 0;
+
+    for &value in nurikabe.data.iter() {
+        let clue = match value {
+            1..=15 => format!("{:x}", value),
+            16..=255 => format!("-{:x}", value),
+            256..=4095 => format!("+{:x}", value),
+            4096..=8191 => format!("={:03x}", value - 4096),
+            8192..=12287 => format!("%{:03x}", value - 8192),
+            _ => {
+                skipped += 1;
+                if skipped == 20 {
+                    body.push('z');
+                    skipped = 0;
+                }
+                continue;
+            }
+        };
+
+        if skipped > 0 {
+            body.push((b'f' + skipped) as char);
+            skipped = 0;
+        }
+        body.push_str(&clue);
+    }
+    if skipped > 0 {
+        body.push((b'f' + skipped) as char);
+    }
+
+    format!(
+        "https://puzz.link/p?nurikabe/{}/{}/{}",
+        nurikabe.width, nurikabe.height, body
+    )
+}
+
+/// Tells if the input is a pzprv3 file, `load_pzprv3` reads it.
+///
+pub fn is_pzprv3(input: &str) -> bool {
+    input.trim_start().starts_with("pzprv3")
+}
+
+/// Reads a puzzle from the pzprv3 file format of pzprjs. After the header
+/// (`pzprv3`, `nurikabe`, rows and columns) come the rows of cells: a number
+/// is a clue, `-` a clue with a question mark, `#` a shaded (black) cell, `+`
+/// a dot (white cell) and `.` an empty cell. Question marks are read as white
+/// cells, the puzzle has no way to keep a clue of unknown size.
+///
+pub fn load_pzprv3(input: &str) -> Result<Nurikabe, ParseError> {
+    let lines = input
         .lines()
-        .map(|line: &str| -> Result<Vec<_>, _> {
-            let values = line
-                .split(',')
-                .map(|v| {
-                    v.trim()
-                        .parse::<i32>()
-                        .map(|v| if v <= 0 { UNKNOWN } else { v })
-                })
-                .collect::<Result<Vec<_>, _>>();
+        .enumerate()
+        .map(|(k, line)| (k + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect::<Vec<_>>();
+    let line = |k: usize| {
+        lines
+            .get(k)
+            .map_or(input.lines().count() + 1, |&(line, _)| line)
+    };
+    let invalid = |k: usize, reason: String| ParseError::InvalidPzprv3 {
+        line: line(k),
+        reason,
+    };
+    let text = |k: usize, what: &str| {
+        lines
+            .get(k)
+            .map(|&(_, text)| text)
+            .ok_or_else(|| invalid(k, format!("missing {}", what)))
+    };
 
-            if values.is_ok() && width == 0 {
-                width = values.as_ref().ok().unwrap().len();
+    if !text(0, "header")?.starts_with("pzprv3") {
+        return Err(invalid(0, String::from("missing pzprv3 header")));
+    }
+
+    let kind = text(1, "puzzle type")?;
+    if kind != "nurikabe" {
+        return Err(invalid(1, format!("'{}' is not a nurikabe", kind)));
+    }
+
+    let size = |k: usize, what: &str| {
+        let value = text(k, what)?;
+        value
+            .parse::<usize>()
+            .map_err(|_| invalid(k, format!("'{}' is not a number of {}", value, what)))
+    };
+    let height = size(2, "rows")?;
+    let width = size(3, "columns")?;
+
+    let row = |k: usize, what: &str| {
+        let values = text(k, what)?.split_whitespace().collect::<Vec<_>>();
+        if values.len() != width {
+            return Err(ParseError::Ragged {
+                line: line(k),
+                expected: width,
+                found: values.len(),
+            });
+        }
+        Ok(values)
+    };
+
+    let mut rows = vec![];
+    let mut marks = vec![];
+    for k in 4..4 + height {
+        let mut clues = vec![];
+
+        for (column, value) in row(k, "row of cells")?.into_iter().enumerate() {
+            let (clue, mark) = match value {
+                "." => (0, State::Unknown),
+                "#" => (0, State::Black),
+                "+" => (0, State::White),
+                // Clue with a question mark, its island has an unknown size.
+                "-" => (0, State::White),
+                _ => (parse_clue(line(k), column + 1, value)?, State::Unknown),
+            };
+
+            clues.push(clue);
+            marks.push(mark);
+        }
+
+        rows.push((line(k), clues));
+    }
+
+    // pzprjs may add its history after the cells, it's ignored.
+    let mut nurikabe = from_rows(rows)?;
+
+    for (value, mark) in nurikabe.data.iter_mut().zip(marks) {
+        if mark != State::Unknown {
+            *value = mark.into();
+        }
+    }
+
+    Ok(nurikabe)
+}
+
+/// Puzzle in the pzprv3 file format of pzprjs, black cells are written as
+/// shaded and white cells as dots.
+///
+pub fn to_pzprv3(nurikabe: &Nurikabe) -> String {
+    let mut text = format!(
+        "pzprv3\nnurikabe\n{}\n{}\n",
+        nurikabe.height, nurikabe.width
+    );
+
+    for row in nurikabe.data.chunks(nurikabe.width.max(1)) {
+        for &value in row {
+            match value {
+                1.. => text.push_str(&format!("{} ", value)),
+                BLACK => text.push_str("# "),
+                WHITE => text.push_str("+ "),
+                _ => text.push_str(". "),
+            }
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Puzzles of the `data` directory, with their file names.
+    ///
+    pub(crate) fn data_puzzles() -> Vec<(String, Nurikabe)> {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/data");
+        let mut paths = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "csv"))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        paths
+            .into_iter()
+            .map(|path| {
+                let input = fs::read_to_string(&path).unwrap();
+                let name = path.display().to_string();
+                let nurikabe = load_nurikabe(&input).unwrap_or_else(|e| panic!("{}: {}", name, e));
+                (name, nurikabe)
+            })
+            .collect()
+    }
+
+    /// Grid from rows of `#` black, `.` white, `?` unknown and clue digits.
+    ///
+    pub(crate) fn grid(rows: &[&str]) -> Nurikabe {
+        let data = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|c| match c {
+                '#' => BLACK,
+                '.' => WHITE,
+                '?' => UNKNOWN,
+                c => c.to_digit(10).unwrap() as i32,
+            })
+            .collect();
+
+        Nurikabe::new(rows[0].len(), rows.len(), data)
+    }
+
+    /// Puzzle without a solution, both islands would touch.
+    ///
+    pub(crate) fn unsolvable_puzzle() -> Nurikabe {
+        load_nurikabe("2,0\n0,2").unwrap()
+    }
+
+    /// Puzzle of the `data` directory with the given file name.
+    ///
+    pub(crate) fn data_puzzle(name: &str) -> Nurikabe {
+        let path = format!("{}/data/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let input = fs::read_to_string(&path).unwrap();
+        load_nurikabe(&input).unwrap_or_else(|e| panic!("{}: {}", path, e))
+    }
+
+    #[test]
+    fn pzprv3_reads_pzprjs_file() {
+        // Saved by pzprjs, with a shaded cell, dots, a question mark and history.
+        let input = "pzprv3\nnurikabe\n5\n5\n. 5 # . . \n+ . 2 . . \n. . . . . \n- . 1 . . \n. . . 3 . \nhistory:{\"type\":\"pzpr\",\"version\":0.4}\n";
+
+        assert!(is_pzprv3(input));
+        let nurikabe = parse_nurikabe(input).unwrap();
+
+        assert_eq!((nurikabe.width, nurikabe.height), (5, 5));
+        #[rustfmt::skip]
+        assert_eq!(nurikabe.data, vec![
+            -3,  5, -1, -3, -3,
+            -2, -3,  2, -3, -3,
+            -3, -3, -3, -3, -3,
+            -2, -3,  1, -3, -3,
+            -3, -3, -3,  3, -3,
+        ]);
+    }
+
+    #[test]
+    fn pzprv3_round_trip() {
+        for (name, nurikabe) in data_puzzles() {
+            let text = to_pzprv3(&nurikabe);
+            assert_eq!(load_pzprv3(&text).unwrap(), nurikabe, "{}", name);
+        }
+
+        // Marks of a player are kept.
+        let marked = Nurikabe::new(3, 2, vec![2, -2, -1, -1, -1, -3]);
+        let text = to_pzprv3(&marked);
+        assert_eq!(text, "pzprv3\nnurikabe\n2\n3\n2 + # \n# # . \n");
+        assert_eq!(load_pzprv3(&text).unwrap(), marked);
+    }
+
+    #[test]
+    fn pzprv3_errors() {
+        let error = |input: &str| load_pzprv3(input).unwrap_err();
+
+        assert_eq!(
+            error("pzprv3\nsudoku\n2\n2\n. . \n. . \n"),
+            ParseError::InvalidPzprv3 {
+                line: 2,
+                reason: String::from("'sudoku' is not a nurikabe"),
+            }
+        );
+        assert_eq!(
+            error("pzprv3\nnurikabe\n2\n2\n1 # \n+ \n"),
+            ParseError::Ragged {
+                line: 6,
+                expected: 2,
+                found: 1,
+            }
+        );
+        assert_eq!(
+            error("pzprv3\nnurikabe\n2\n2\n1 x \n. . \n"),
+            ParseError::InvalidValue {
+                line: 5,
+                column: 2,
+                value: String::from("x"),
             }
-            height += 1;
+        );
+    }
+
+    #[test]
+    fn csv_reads_clues() {
+        let nurikabe = load_nurikabe("2,0\n\n0, 1\n\n").unwrap();
+
+        assert_eq!((nurikabe.width, nurikabe.height), (2, 2));
+        assert_eq!(nurikabe.data, vec![2, -3, -3, 1]);
+    }
+
+    #[test]
+    fn csv_errors() {
+        let error = |input: &str| load_nurikabe(input).unwrap_err();
 
-            values
-        })
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|report| format!("Make shour the data is correctly formatted!\n {}", report))?;
+        assert_eq!(error(""), ParseError::Empty);
+        assert_eq!(error("\n  \n"), ParseError::Empty);
+        assert_eq!(
+            error("1,0\n0,x"),
+            ParseError::InvalidValue {
+                line: 2,
+                column: 2,
+                value: String::from("x"),
+            }
+        );
+        assert_eq!(
+            error("0,0\n\n0,-1"),
+            ParseError::Negative {
+                line: 3,
+                column: 2,
+                value: -1,
+            }
+        );
+        assert_eq!(
+            error("1,0,0\n0,0"),
+            ParseError::Ragged {
+                line: 2,
+                expected: 3,
+                found: 2,
+            }
+        );
+        assert_eq!(
+            error("0,0\n5,0"),
+            ParseError::ClueTooLarge {
+                line: 2,
+                column: 1,
+                clue: 5,
+                area: 4,
+            }
+        );
+        assert_eq!(
+            error("3,0\n0,2"),
+            ParseError::CluesExceedArea { sum: 5, area: 4 }
+        );
+    }
 
-    Ok(Nurikabe::new(width, height, input.concat()))
+    #[test]
+    fn errors_name_line_and_column() {
+        let error = load_nurikabe("0,0\n0,x").unwrap_err();
+
+        assert_eq!(error.to_string(), "Line 2, column 2: 'x' is not a number");
+        assert_eq!(String::from(error), "Line 2, column 2: 'x' is not a number");
+    }
+
+    #[test]
+    fn url_round_trip() {
+        for (name, nurikabe) in data_puzzles() {
+            let url = to_url(&nurikabe);
+
+            assert!(is_url(&url), "{}", name);
+            assert_eq!(load_url(&url).unwrap(), nurikabe, "{}", name);
+        }
+    }
+
+    #[test]
+    fn url_reads_puzz_link() {
+        let nurikabe = load_url("https://puzz.link/p?nurikabe/5/5/g5k2o1k3g").unwrap();
+
+        assert_eq!((nurikabe.width, nurikabe.height), (5, 5));
+        #[rustfmt::skip]
+        assert_eq!(nurikabe.data.iter().map(|&v| v.max(0)).collect::<Vec<_>>(), vec![
+            0, 5, 0, 0, 0,
+            0, 0, 2, 0, 0,
+            0, 0, 0, 0, 0,
+            0, 0, 1, 0, 0,
+            0, 0, 0, 3, 0,
+        ]);
+
+        // Same puzzle on pzv.jp, with a flag before the size.
+        let same = load_url("http://pzv.jp/p.html?nurikabe/v:/5/5/g5k2o1k3g").unwrap();
+        assert_eq!(same, nurikabe);
+    }
+
+    #[test]
+    fn url_encodes_big_clues() {
+        let mut data = vec![-3; 130 * 130];
+        data[0] = 17;
+        data[1] = 300;
+        data[2] = 5000;
+        data[3] = 9000;
+        let nurikabe = Nurikabe::new(130, 130, data);
+
+        let url = to_url(&nurikabe);
+        assert!(url.starts_with("https://puzz.link/p?nurikabe/130/130/-11+12c=388%328z"));
+        assert_eq!(load_url(&url).unwrap(), nurikabe);
+    }
+
+    #[test]
+    fn url_errors() {
+        let error = |url: &str| load_url(url).unwrap_err();
+        let invalid = |reason: &str| ParseError::InvalidUrl(String::from(reason));
+
+        assert_eq!(error("p?sudoku/5/5/g"), invalid("not a nurikabe"));
+        assert_eq!(error("p?nurikabe/5"), invalid("missing board size"));
+        assert_eq!(error("p?nurikabe/5/5/-1"), invalid("truncated number"));
+        assert_eq!(error("p?nurikabe/5/5/g_"), invalid("unexpected '_'"));
+        assert_eq!(
+            error("p?nurikabe/2/2/-1a"),
+            ParseError::ClueTooLarge {
+                line: 1,
+                column: 1,
+                clue: 26,
+                area: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn url_is_not_a_path() {
+        assert!(is_url("https://puzz.link/p?nurikabe/5/5/g5k2o1k3g"));
+        assert!(is_url("p?nurikabe/5/5/g5k2o1k3g"));
+        assert!(!is_url("/tmp/nurikabe/puzzle.csv"));
+        assert!(!is_url("data/nurikabe1.csv"));
+    }
 }
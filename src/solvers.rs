@@ -1,42 +1,25 @@
 pub mod aco;
+pub mod ant;
+pub mod backtrack;
+pub mod board;
+pub mod cdcl;
+pub mod difficulty;
+pub mod hint;
 pub mod naive;
 pub mod random_ant;
+pub mod rng;
+pub mod sat;
 pub mod state;
+pub mod unique;
 
 use std::fmt::Debug;
-use std::ops::{Bound, Range, RangeBounds};
 
+use rng::Rng;
 use state::State;
-use wasm_bindgen::JsValue;
 
 pub use crate::nurikabe::Nurikabe;
 pub use crate::solvers::naive::NaiveSolver;
 
-fn random_int(range: Range<usize>) -> usize {
-    let mut dest: [u8; 1] = [0];
-    getrandom::getrandom(&mut dest).expect("Random");
-    let lower = match range.start_bound() {
-        Bound::Included(&lower) => lower,
-        _ => 0,
-    };
-    let dist = match range.end_bound() {
-        Bound::Excluded(&end) => end - lower,
-        _ => 0,
-    };
-
-    lower + ((dest[0] as f32 / 255.1) * dist as f32).floor() as usize
-}
-
-fn random_float() -> f64 {
-    get_random_buf().expect("Random")[0] as f64 / (255.1)
-}
-
-fn get_random_buf() -> Result<[u8; 1], getrandom::Error> {
-    let mut buf = [0u8; 1];
-    getrandom::getrandom(&mut buf)?;
-    Ok(buf)
-}
-
 #[inline]
 pub fn for_valid_neighbours_with_outside(
     width: usize,
@@ -115,28 +98,16 @@ pub fn for_valid_diagonal_neighbours(
     }
 }
 
-/// Returns false if one neighbour is invalid.
-///
-#[inline]
-pub fn for_none_of_neibhbours(
-    width: usize,
-    height: usize,
-    x: usize,
-    y: usize,
-    mut f: impl FnMut(usize, usize) -> bool,
-) -> bool {
-    (x + 1 < height && f(x + 1, y))
-        || (y + 1 < width && f(x, y + 1))
-        || (x > 0 && f(x - 1, y))
-        || (y > 0 && f(x, y - 1))
-}
-
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
 pub enum Step {
     Contradiction,
     SolutionFound,
     Proceed,
     CannotProceed,
+    /// Stopped by the cancellation signal of a `Session`.
+    Cancelled,
+    /// Stopped by the time limit of a `Session`.
+    Timeout,
 }
 
 pub trait Solver {
@@ -144,11 +115,19 @@ pub trait Solver {
     ///
     fn solve(&mut self) -> Step;
 
-    /// Returns current state of solver for presentation on the JS/view side.
+    /// Returns a snapshot of the current state of solver. The JS/view side
+    /// converts it in `wasm.rs`.
     ///
-    fn get_state(&self) -> JsValue;
+    fn get_state(&self) -> Nurikabe;
 
     /// Current solving iteration.
     ///
     fn get_iteration(&self) -> usize;
+
+    /// Score of the best state so far, lower is better and `0` is solved.
+    /// Only the ant solvers keep one.
+    ///
+    fn get_score(&self) -> Option<usize> {
+        None
+    }
 }
@@ -0,0 +1,648 @@
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::board::{Board, Cells};
+use super::state::{BLACK, WHITE};
+use super::*;
+
+/// Picks the next cell an ant adds to the island it grows. Strategies are
+/// the only difference between the ant solvers, the construction of a grid
+/// is shared in `Colony::construct`.
+///
+pub trait Strategy {
+    /// Position in `queue` of the next candidate cell. `queue` is never empty.
+    fn pick(&mut self, queue: &[usize], rng: &mut Rng) -> usize;
+
+    /// Called when `cell` is added to the island with the clue at `clue`.
+    fn visit(&mut self, _board: &Board, _cell: usize, _clue: usize) {}
+}
+
+/// Every candidate is equally likely.
+///
+#[derive(Clone, Debug)]
+pub struct Uniform;
+
+impl Strategy for Uniform {
+    fn pick(&mut self, queue: &[usize], rng: &mut Rng) -> usize {
+        rng.random_int(0..queue.len())
+    }
+}
+
+/// Phermon of every cell. Visited cells get a local phermon update, that
+/// favours cells away from the clue.
+///
+#[derive(Clone, Debug)]
+pub struct Phermons {
+    pub values: Vec<f64>,
+    pub l_evap: f64,
+    pub evap: f64,
+}
+
+impl Phermons {
+    fn visit(&mut self, board: &Board, cell: usize, clue: usize) {
+        let dist = {
+            let (x, y) = board.pos(cell);
+            let pos = board.pos(clue);
+            let xdis = pos.0.abs_diff(x);
+            let ydis = pos.1.abs_diff(y);
+            let dist = ((xdis * xdis + ydis * ydis) as f64).sqrt();
+            1.0 - 1.0 / (dist - 0.8).exp()
+        };
+
+        let p = self.l_evap;
+        self.values[cell] = (1.0 - p) * self.values[cell] + p * (self.evap * dist);
+    }
+}
+
+/// Candidates are picked with probability proportional to their phermon.
+///
+#[derive(Clone, Debug)]
+pub struct Roulette(pub Phermons);
+
+impl Strategy for Roulette {
+    fn pick(&mut self, queue: &[usize], rng: &mut Rng) -> usize {
+        roulette(queue, &self.0.values, rng)
+    }
+
+    fn visit(&mut self, board: &Board, cell: usize, clue: usize) {
+        self.0.visit(board, cell, clue);
+    }
+}
+
+/// Always picks the candidate with the most phermon.
+///
+#[derive(Clone, Debug)]
+pub struct Greedy(pub Phermons);
+
+impl Strategy for Greedy {
+    fn pick(&mut self, queue: &[usize], _rng: &mut Rng) -> usize {
+        greedy(queue, &self.0.values)
+    }
+
+    fn visit(&mut self, board: &Board, cell: usize, clue: usize) {
+        self.0.visit(board, cell, clue);
+    }
+}
+
+/// Ant colony optimisation, used by `AntSolver`. Picks like `Greedy` with
+/// probability `greedines`, otherwise like `Roulette`.
+///
+#[derive(Clone, Debug)]
+pub struct Aco {
+    pub phermons: Phermons,
+    pub greedines: f64,
+}
+
+impl Strategy for Aco {
+    fn pick(&mut self, queue: &[usize], rng: &mut Rng) -> usize {
+        if rng.random_float() < self.greedines {
+            greedy(queue, &self.phermons.values)
+        } else {
+            roulette(queue, &self.phermons.values, rng)
+        }
+    }
+
+    fn visit(&mut self, board: &Board, cell: usize, clue: usize) {
+        self.phermons.visit(board, cell, clue);
+    }
+}
+
+/// Candidate with the biggest weight, the first one on ties.
+///
+fn greedy(queue: &[usize], weights: &[f64]) -> usize {
+    let mut pick = 0.0;
+    let mut index = 0;
+    for (k, &j) in queue.iter().enumerate() {
+        if weights[j] > pick {
+            pick = weights[j];
+            index = k;
+        }
+    }
+    index
+}
+
+/// Candidate picked with probability proportional to its weight.
+///
+fn roulette(queue: &[usize], weights: &[f64], rng: &mut Rng) -> usize {
+    let r = rng.random_float();
+    let sum: f64 = queue.iter().map(|&j| weights[j]).sum();
+
+    let mut acc = 0.0;
+    queue
+        .iter()
+        .position(|&j| {
+            acc += weights[j] / (sum + 1e-10);
+            r < acc
+        })
+        .unwrap_or(queue.len() - 1)
+}
+
+/// Island grown by an ant, starting at its clue.
+///
+#[derive(Debug, Clone)]
+struct Island {
+    id: i32,
+    enclosed: bool,
+    /// Cell of the clue.
+    pos: usize,
+    size: usize,
+    final_size: usize,
+}
+
+impl Island {
+    fn new(id: i32, pos: usize, size: usize, final_size: usize) -> Self {
+        Self {
+            id,
+            enclosed: false,
+            pos,
+            size,
+            final_size,
+        }
+    }
+}
+
+/// Grid built by one ant. Every cell is black until an island takes it.
+///
+#[derive(Debug, Clone)]
+pub(super) struct Grid {
+    pub board: Board,
+    /// Island of each white cell, `0` for black cells.
+    ids: Vec<i32>,
+    reached_white: usize,
+    eval: usize,
+    pub best_p: f64,
+}
+
+impl Grid {
+    fn new(board: Board, ids: Vec<i32>) -> Self {
+        Self {
+            board,
+            ids,
+            reached_white: 0,
+            eval: usize::MAX,
+            best_p: 0.0,
+        }
+    }
+
+    fn empty() -> Self {
+        Self::new(Board::new(0, 0), vec![])
+    }
+
+    /// Starting grid of the puzzle with only the clues white, its islands and
+    /// the number of white cells of the solution.
+    ///
+    fn from_nurikabe(nurikabe: &Nurikabe) -> (Self, Vec<Island>, usize) {
+        let mut board = Board::new(nurikabe.width, nurikabe.height);
+        let mut ids = vec![0; board.len()];
+        let mut islands = vec![];
+        let mut num_white = 0;
+
+        for (i, &val) in nurikabe.data.iter().enumerate() {
+            if val > 0 {
+                num_white += val as usize;
+                ids[i] = islands.len() as i32 + 1;
+                islands.push(Island::new(ids[i], i, 0, val as usize));
+                board.set(i, State::White);
+            } else {
+                board.set(i, State::Black);
+            }
+        }
+
+        (Self::new(board, ids), islands, num_white)
+    }
+
+    /// Adds the cell to the island.
+    ///
+    #[inline]
+    fn set_island(&mut self, i: usize, island: &Island) {
+        self.ids[i] = island.id;
+        self.board.set(i, State::White);
+    }
+
+    /// Checks if there is a a possible connection with a different island.
+    ///
+    fn is_connecting_islands(&self, i: usize, island_id: i32) -> bool {
+        self.board
+            .neighbours(i)
+            .iter()
+            .any(|&j| self.board.is_white(j) && self.ids[j] != island_id)
+    }
+
+    /// Easy test first, which checks for specific situation, when a fragmentation
+    /// of the river can occur (river splits in two separate parts). If a situation
+    /// is found, than one of the possible start positions for a deapth first search
+    /// (DFS) check is returned.
+    ///
+    fn is_river_frgmented(&mut self, i: usize, island: &mut Island) -> bool {
+        if let Some(start) = self.cut_creates_frgments(i, island) {
+            assert!(self.board.is_black(start), "Problem!");
+
+            // Set white.
+            self.set_island(i, island);
+            self.reached_white += 1;
+
+            let reached_black_cells = self.board.flood(start, |j| self.board.is_black(j)).len();
+            let num_black_cells = self.board.len() - self.reached_white;
+
+            let in_fragments = reached_black_cells != num_black_cells;
+
+            // Restore.
+            self.ids[i] = 0;
+            self.board.set(i, State::Black);
+            self.reached_white -= 1;
+
+            in_fragments
+        } else {
+            false
+        }
+    }
+
+    /// Checks for ossible situation when cuts in the river can make isolated
+    /// rivers.
+    ///
+    fn cut_creates_frgments(&self, i: usize, island: &mut Island) -> Option<usize> {
+        let surrounding = self.board.surrounding(i);
+
+        // Corner case. Literally. :)
+        if surrounding.len() == 3 {
+            return None;
+        }
+
+        let mut prev = None;
+        let skips = surrounding.iter().fold(0, |mut s, &j| {
+            let cell = self.board.is_white(j);
+            if let Some(pre) = prev {
+                if pre != cell {
+                    s += 1;
+                }
+            }
+            prev = Some(cell);
+            s
+        });
+
+        if skips > 2 || island.enclosed {
+            island.enclosed = true;
+
+            let (x, y) = self.board.pos(i);
+
+            for &d in self.board.diagonal(i) {
+                let (a, b) = self.board.pos(d);
+                let (column, row) = (self.board.index(a, y), self.board.index(x, b));
+
+                if self.board.is_black(column) {
+                    return Some(column);
+                } else if self.board.is_black(row) {
+                    return Some(row);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Marks the island as enclosed, when the cell touches a white cell by a
+    /// corner.
+    ///
+    fn update_enclosed(&self, i: usize, island: &mut Island) {
+        if self
+            .board
+            .diagonal(i)
+            .iter()
+            .any(|&j| self.board.is_white(j))
+        {
+            island.enclosed = true;
+        }
+    }
+
+    fn evaluate(&mut self, white_cells: usize) -> f64 {
+        self.eval = white_cells - self.reached_white + self.board.num_pools();
+        self.best_p = 1.0 / self.eval as f64;
+        self.best_p
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.eval == 0
+    }
+
+    /// Cells in the format of `Nurikabe`, clues keep their size.
+    ///
+    fn to_data(&self, islands: &[Island]) -> Vec<i32> {
+        (0..self.board.len())
+            .map(|i| {
+                if self.board.is_black(i) {
+                    BLACK
+                } else {
+                    match islands.iter().find(|island| island.pos == i) {
+                        Some(island) => island.final_size as i32,
+                        None => WHITE,
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Puzzle, best grid found so far and the random generator, shared by the ant
+/// solvers.
+///
+#[derive(Debug)]
+pub(super) struct Colony {
+    path: String,
+    /// Starting grid, only the clues are white.
+    pub grid: Grid,
+    pub solution: Grid,
+    /// White cells of a solution.
+    num_white: usize,
+    islands: Vec<Island>,
+    seed: u64,
+    pub rng: Rng,
+    pub iteration: usize,
+    explain: String,
+}
+
+impl Colony {
+    pub fn new(seed: u64, nurikabe: Nurikabe) -> Self {
+        let (grid, islands, num_white) = Grid::from_nurikabe(&nurikabe);
+
+        Self {
+            path: nurikabe.path,
+            grid,
+            solution: Grid::empty(),
+            num_white,
+            islands,
+            seed,
+            rng: Rng::new(seed),
+            iteration: 0,
+            explain: String::new(),
+        }
+    }
+
+    /// Builds the grids of `ants` ants, each with a copy of the strategy.
+    /// Every ant has its own random stream, seeded from the colony generator,
+    /// so the result is the same for a seed with any number of threads.
+    ///
+    pub fn construct_all<S: Strategy + Send>(
+        &mut self,
+        ants: usize,
+        strategy: impl Fn() -> S + Sync,
+    ) -> Vec<(Grid, S)> {
+        let seeds = (0..ants).map(|_| self.rng.next_u64()).collect::<Vec<_>>();
+
+        let construct = |seed: u64| {
+            let mut strategy = strategy();
+            let grid = self.construct(&mut strategy, &mut Rng::new(seed));
+            (grid, strategy)
+        };
+
+        #[cfg(feature = "parallel")]
+        let grids = seeds.into_par_iter().map(construct).collect();
+        #[cfg(not(feature = "parallel"))]
+        let grids = seeds.into_iter().map(construct).collect();
+
+        grids
+    }
+
+    /// Builds the grid of one ant. Islands are grown in random order, each
+    /// from its clue, with cells chosen by the strategy. Cells that would
+    /// connect two islands or split the river are skipped.
+    ///
+    fn construct(&self, strategy: &mut impl Strategy, rng: &mut Rng) -> Grid {
+        let mut islands = self.islands.clone();
+        let mut grid = self.grid.clone();
+        grid.reached_white = islands.len();
+
+        while !islands.is_empty() {
+            let mut island = islands.remove(rng.random_int(0..islands.len()));
+            let mut queue = vec![];
+            let mut queued = Cells::new(grid.board.len());
+            let mut cell = island.pos;
+
+            loop {
+                // Cell is valid. Update the current ant grid.
+
+                grid.set_island(cell, &island);
+                strategy.visit(&grid.board, cell, island.pos);
+
+                island.size += 1;
+                if island.size >= island.final_size {
+                    break;
+                }
+
+                for &j in grid.board.neighbours(cell) {
+                    if grid.board.is_black(j) && !queued.contains(j) {
+                        queued.insert(j);
+                        queue.push(j);
+                    }
+                }
+
+                grid.update_enclosed(cell, &mut island);
+
+                let next = loop {
+                    if queue.is_empty() {
+                        break None;
+                    }
+
+                    let i = queue.remove(strategy.pick(&queue, rng));
+                    if !grid.is_connecting_islands(i, island.id)
+                        && !grid.is_river_frgmented(i, &mut island)
+                    {
+                        break Some(i);
+                    }
+                };
+
+                match next {
+                    Some(i) => {
+                        grid.reached_white += 1;
+                        cell = i;
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        grid
+    }
+
+    /// Keeps the grid of an ant if it's better than the best one so far.
+    /// Returns true if it was kept.
+    ///
+    pub fn offer(&mut self, grid: &mut Grid) -> bool {
+        self.iteration += 1;
+
+        if grid.evaluate(self.num_white) <= self.solution.best_p {
+            return false;
+        }
+
+        self.solution.clone_from(grid);
+        self.explain = if self.solution.is_solved() {
+            format!("Puzzle solved! ({}/{})", grid.reached_white, self.num_white)
+        } else {
+            format!("Found current best solution is {}", self.solution.eval)
+        };
+
+        true
+    }
+
+    /// White cells the best grid misses, plus its pools. `None` before the
+    /// first ant.
+    ///
+    pub fn score(&self) -> Option<usize> {
+        (self.solution.eval != usize::MAX).then_some(self.solution.eval)
+    }
+
+    pub fn get_state(&self, verbose: bool) -> Nurikabe {
+        let verbose = if verbose {
+            self.explain.clone()
+        } else {
+            String::from("")
+        };
+
+        Nurikabe {
+            path: self.path.clone(),
+            width: self.solution.board.width,
+            height: self.solution.board.height,
+            solved: self.solution.is_solved(),
+            iteration: self.iteration,
+            data: self.solution.to_data(&self.islands),
+            duration: 0,
+            seed: Some(self.seed),
+            verbose,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::tests::data_puzzle;
+
+    fn phermons(values: Vec<f64>) -> Phermons {
+        Phermons {
+            values,
+            l_evap: 0.1,
+            evap: 0.5,
+        }
+    }
+
+    #[test]
+    fn greedy_picks_most_phermon() {
+        let mut strategy = Greedy(phermons(vec![0.1, 0.7, 0.3, 0.7]));
+        let mut rng = Rng::new(1);
+
+        assert_eq!(strategy.pick(&[0, 2, 3, 1], &mut rng), 2);
+        assert_eq!(strategy.pick(&[0, 2], &mut rng), 1);
+    }
+
+    #[test]
+    fn roulette_skips_cells_without_phermon() {
+        let mut strategy = Roulette(phermons(vec![0.0, 1.0, 0.0, 3.0]));
+        let mut rng = Rng::new(1);
+        let queue = [0, 1, 2, 3];
+
+        let mut picks = [0; 4];
+        for _ in 0..1000 {
+            picks[strategy.pick(&queue, &mut rng)] += 1;
+        }
+
+        assert_eq!(picks[0] + picks[2], 0);
+        assert!(picks[3] > 2 * picks[1], "{:?}", picks);
+    }
+
+    #[test]
+    fn uniform_picks_every_candidate() {
+        let mut rng = Rng::new(1);
+        let mut picks = [0; 3];
+        for _ in 0..300 {
+            picks[Uniform.pick(&[5, 6, 7], &mut rng)] += 1;
+        }
+
+        assert!(picks.iter().all(|&n| n > 50), "{:?}", picks);
+    }
+
+    #[test]
+    fn aco_greedy_when_greedines_is_one() {
+        let values = vec![0.2, 0.5, 0.1];
+        let mut aco = Aco {
+            phermons: phermons(values.clone()),
+            greedines: 1.0,
+        };
+        let mut greedy = Greedy(phermons(values));
+        let mut rng = Rng::new(1);
+
+        assert_eq!(
+            aco.pick(&[0, 1, 2], &mut rng),
+            greedy.pick(&[0, 1, 2], &mut rng)
+        );
+    }
+
+    #[test]
+    fn visit_updates_phermon() {
+        let board = Board::new(3, 3);
+        let mut strategy = Roulette(phermons(vec![1.0; 9]));
+
+        strategy.visit(&board, 8, 0);
+        assert!(strategy.0.values[8] < 1.0);
+        assert_eq!(strategy.0.values[..8], [1.0; 8]);
+    }
+
+    #[test]
+    fn construct_with_any_strategy() {
+        let nurikabe = data_puzzle("nurikabe5x5.csv");
+        let mut colony = Colony::new(1, nurikabe.clone());
+        let values = phermons(vec![0.5; nurikabe.data.len()]);
+
+        let greedy = Greedy(values.clone());
+        let roulette = Roulette(values);
+        let grids = [
+            colony.construct_all(1, || Uniform).remove(0).0,
+            colony.construct_all(1, || greedy.clone()).remove(0).0,
+            colony.construct_all(1, || roulette.clone()).remove(0).0,
+        ];
+
+        let white = nurikabe.data.iter().filter(|&&v| v > 0).count();
+        for grid in grids {
+            let mut clues = (0..grid.board.len()).filter(|&i| nurikabe.data[i] > 0);
+            assert!(clues.all(|i| grid.board.is_white(i)));
+            assert!(grid.board.white().iter().count() > white);
+        }
+    }
+
+    /// Cells of the grids built by the ants of `construct_all`.
+    ///
+    fn construct_all(seed: u64) -> Vec<Vec<i32>> {
+        let mut colony = Colony::new(seed, data_puzzle("nurikabe10x10v1.csv"));
+        let grids = colony.construct_all(8, || Uniform);
+        grids
+            .iter()
+            .map(|(grid, _)| grid.to_data(&colony.islands))
+            .collect()
+    }
+
+    #[test]
+    fn construct_all_matches_sequential_ants() {
+        let colony = Colony::new(3, data_puzzle("nurikabe10x10v1.csv"));
+        let mut rng = colony.rng.clone();
+        let sequential = (0..8)
+            .map(|_| {
+                let grid = colony.construct(&mut Uniform, &mut Rng::new(rng.next_u64()));
+                grid.to_data(&colony.islands)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(construct_all(3), sequential);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn construct_all_same_with_any_threads() {
+        let run = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| construct_all(3))
+        };
+
+        assert_eq!(run(1), run(4));
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::VecDeque;
+
+use super::state::{BLACK, UNKNOWN, WHITE};
+use super::*;
+use crate::verify::verify;
+
+/// Complete solver. Grid is propagated with the rules of `NaiveSolver` and
+/// when they can't proceed, the colour of one unknown cell is guessed (after
+/// probing for cells with only one possible colour). Guesses that lead to a
+/// contradiction are undone, so the puzzle is either solved or proven to have
+/// no solution.
+///
+#[derive(Debug)]
+pub struct BacktrackSolver {
+    path: String,
+    width: usize,
+    height: usize,
+    clues: Vec<i32>,
+    /// Grids still to explore, the last one is explored first.
+    stack: Vec<Vec<i32>>,
+    current: Vec<i32>,
+    solution: Option<Vec<i32>>,
+    step: Step,
+    iteration: usize,
+    /// Cells forced by probing.
+    probes: usize,
+    /// Cells guessed when probing found nothing.
+    guesses: usize,
+    explenation: String,
+    pub verbose: bool,
+}
+
+impl BacktrackSolver {
+    pub fn new(nurikabe: Nurikabe) -> Self {
+        let clues = nurikabe
+            .data
+            .iter()
+            .map(|&v| if v > 0 { v } else { UNKNOWN })
+            .collect::<Vec<_>>();
+
+        Self {
+            path: nurikabe.path,
+            width: nurikabe.width,
+            height: nurikabe.height,
+            stack: vec![nurikabe.data.clone()],
+            current: clues.clone(),
+            clues,
+            solution: None,
+            step: Step::Proceed,
+            iteration: 0,
+            probes: 0,
+            guesses: 0,
+            explenation: String::new(),
+            verbose: false,
+        }
+    }
+
+    /// Continues the search after a solution was found, to look for another
+    /// one.
+    ///
+    pub fn resume(&mut self) {
+        if self.step == Step::SolutionFound {
+            self.step = Step::Proceed;
+            self.solution = None;
+        }
+    }
+
+    /// Number of cells forced by probing so far.
+    ///
+    pub fn get_probes(&self) -> usize {
+        self.probes
+    }
+
+    /// Number of guesses made so far.
+    ///
+    pub fn get_guesses(&self) -> usize {
+        self.guesses
+    }
+
+    /// Rule solver reports island cells with the size of their island. Only
+    /// clues keep their value, other white cells become `WHITE`.
+    ///
+    fn normalise(&self, data: Vec<i32>) -> Vec<i32> {
+        data.into_iter()
+            .zip(self.clues.iter())
+            .map(|(v, &clue)| match v {
+                _ if clue > 0 => clue,
+                BLACK | UNKNOWN => v,
+                _ => WHITE,
+            })
+            .collect()
+    }
+
+    /// Propagates the rules until they can't proceed any more.
+    ///
+    fn propagate(&self, data: Vec<i32>) -> (Step, Vec<i32>) {
+        let mut solver = NaiveSolver::new(Nurikabe::new(self.width, self.height, data));
+
+        let step = loop {
+            let step = solver.solve();
+            if step != Step::Proceed {
+                break step;
+            }
+        };
+
+        (step, self.normalise(solver.get_state().data))
+    }
+
+    /// Propagates the grid and checks the result. Contradiction is returned
+    /// for any grid that can't lead to a solution.
+    ///
+    fn explore(&self, data: Vec<i32>) -> (Step, Vec<i32>) {
+        let (step, data) = self.propagate(data);
+
+        let step = match step {
+            Step::SolutionFound if self.is_solution(&data) => Step::SolutionFound,
+            Step::CannotProceed if !self.is_dead_end(&data) => Step::CannotProceed,
+            _ => Step::Contradiction,
+        };
+
+        (step, data)
+    }
+
+    /// Tries both colours on cells next to white cells. When one colour leads
+    /// to a contradiction, the cell has to be of the other colour, which saves
+    /// a guess.
+    ///
+    fn probe(&self, data: &[i32]) -> Option<(usize, i32)> {
+        for (i, &v) in data.iter().enumerate() {
+            if v != UNKNOWN {
+                continue;
+            }
+
+            let mut next_to_white = false;
+            for_valid_neighbours(self.width, self.height, i / self.width, i % self.width, |a, b| {
+                let n = data[a * self.width + b];
+                next_to_white |= n == WHITE || n > 0;
+            });
+
+            if !next_to_white {
+                continue;
+            }
+
+            for (colour, other) in [(WHITE, BLACK), (BLACK, WHITE)] {
+                let mut probe = data.to_vec();
+                probe[i] = colour;
+
+                if self.explore(probe).0 == Step::Contradiction {
+                    return Some((i, other));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Cheap checks the rules don't cover. Black cells have to be able to
+    /// connect through unknown cells and every white cell has to be able to
+    /// reach some clue.
+    ///
+    fn is_dead_end(&self, data: &[i32]) -> bool {
+        let (width, height) = (self.width, self.height);
+
+        let flood = |starts: Vec<usize>, passable: &dyn Fn(i32) -> bool| {
+            let mut reached = vec![false; data.len()];
+            let mut queue = VecDeque::new();
+            for i in starts {
+                reached[i] = true;
+                queue.push_back(i);
+            }
+
+            while let Some(i) = queue.pop_front() {
+                for_valid_neighbours(width, height, i / width, i % width, |a, b| {
+                    let j = a * width + b;
+                    if !reached[j] && passable(data[j]) {
+                        reached[j] = true;
+                        queue.push_back(j);
+                    }
+                });
+            }
+
+            reached
+        };
+
+        if let Some(start) = data.iter().position(|&v| v == BLACK) {
+            let reached = flood(vec![start], &|v| v == BLACK || v == UNKNOWN);
+            if data.iter().zip(reached.iter()).any(|(&v, &r)| v == BLACK && !r) {
+                return true;
+            }
+        }
+
+        let clues = (0..data.len()).filter(|&i| data[i] > 0).collect::<Vec<_>>();
+        let reached = flood(clues.clone(), &|v| v != BLACK);
+        if data.iter().zip(reached.iter()).any(|(&v, &r)| v == WHITE && !r) {
+            return true;
+        }
+
+        // Island of every white cell, white cells without a clue have none.
+        let mut owner = vec![None; data.len()];
+        for &clue in clues.iter() {
+            let island = flood(vec![clue], &|v| v == WHITE || v > 0);
+            for (i, _) in island.iter().enumerate().filter(|(_, &r)| r) {
+                owner[i] = Some(clue);
+            }
+        }
+
+        clues.into_iter().any(|clue| !self.can_grow(data, &owner, clue))
+    }
+
+    /// Checks if the island of the clue can still grow to its size, through
+    /// unknown and unowned white cells that don't touch any other island.
+    ///
+    fn can_grow(&self, data: &[i32], owner: &[Option<usize>], clue: usize) -> bool {
+        let (width, height) = (self.width, self.height);
+        let size = data[clue] as usize;
+
+        let mut reached = owner.iter().map(|&o| o == Some(clue)).collect::<Vec<_>>();
+        let num = reached.iter().filter(|&&r| r).count();
+        if num >= size {
+            return true;
+        }
+
+        let is_other = |j: usize| owner[j].is_some_and(|o| o != clue);
+        let passable = |j: usize| {
+            let mut touches = false;
+            for_valid_neighbours(width, height, j / width, j % width, |a, b| {
+                touches |= is_other(a * width + b);
+            });
+            (data[j] == UNKNOWN || (data[j] == WHITE && owner[j].is_none())) && !touches
+        };
+
+        // Grow by distance, every cell on the way becomes part of the island.
+        let mut queue = (0..data.len())
+            .filter(|&i| reached[i])
+            .map(|i| (i, 0))
+            .collect::<VecDeque<_>>();
+        let mut total = num;
+
+        while let Some((i, dist)) = queue.pop_front() {
+            if num + dist >= size {
+                continue;
+            }
+
+            for_valid_neighbours(width, height, i / width, i % width, |a, b| {
+                let j = a * width + b;
+                if !reached[j] && passable(j) {
+                    reached[j] = true;
+                    total += 1;
+                    queue.push_back((j, dist + 1));
+                }
+            });
+
+            if total >= size {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Picks the unknown cell to guess. A liberty of the unfinished white
+    /// region with the fewest liberties is taken, since it has the fewest
+    /// ways to continue.
+    ///
+    fn pick_guess(&self, data: &[i32]) -> Option<usize> {
+        let (width, height) = (self.width, self.height);
+
+        let mut reached = vec![false; data.len()];
+        let mut best: Option<(usize, usize)> = None;
+
+        for start in 0..data.len() {
+            if reached[start] || !(data[start] == WHITE || data[start] > 0) {
+                continue;
+            }
+
+            let mut size = 0;
+            let mut clue = 0;
+            let mut liberties = vec![];
+            let mut queue = VecDeque::from([start]);
+            reached[start] = true;
+
+            while let Some(i) = queue.pop_front() {
+                size += 1;
+                clue = clue.max(data[i]);
+
+                for_valid_neighbours(width, height, i / width, i % width, |a, b| {
+                    let j = a * width + b;
+                    if data[j] == UNKNOWN {
+                        if !liberties.contains(&j) {
+                            liberties.push(j);
+                        }
+                    } else if !reached[j] && data[j] != BLACK {
+                        reached[j] = true;
+                        queue.push_back(j);
+                    }
+                });
+            }
+
+            let complete = clue > 0 && size >= clue as usize;
+            if complete || liberties.is_empty() {
+                continue;
+            }
+
+            if best.is_none_or(|(num, _)| liberties.len() < num) {
+                best = Some((liberties.len(), liberties[0]));
+            }
+        }
+
+        best.map(|(_, i)| i)
+            .or_else(|| data.iter().position(|&v| v == UNKNOWN))
+    }
+
+    /// Checks all rules of nurikabe on a grid without unknown cells.
+    ///
+    fn is_solution(&self, data: &[i32]) -> bool {
+        let puzzle = Nurikabe::new(self.width, self.height, self.clues.clone());
+        let solution = Nurikabe::new(self.width, self.height, data.to_vec());
+
+        verify(&puzzle, &solution).is_empty()
+    }
+}
+
+impl Solver for BacktrackSolver {
+    fn solve(&mut self) -> Step {
+        if self.step != Step::Proceed {
+            return self.step;
+        }
+
+        let Some(data) = self.stack.pop() else {
+            self.step = Step::Contradiction;
+            self.explenation = String::from("All guesses lead to a contradiction, no solution.");
+            return self.step;
+        };
+
+        self.iteration += 1;
+
+        let (step, data) = self.explore(data);
+        self.current = data;
+
+        match step {
+            Step::SolutionFound => {
+                self.solution = Some(self.current.clone());
+                self.step = Step::SolutionFound;
+                self.explenation = format!("Puzzle solved! (depth {})", self.stack.len());
+            }
+            Step::CannotProceed => {
+                let (x, y) = if let Some((i, colour)) = self.probe(&self.current) {
+                    let mut forced = self.current.clone();
+                    forced[i] = colour;
+                    self.stack.push(forced);
+                    self.probes += 1;
+
+                    self.explenation = format!(
+                        "Cell must be {}, other colour leads to a contradiction",
+                        if colour == WHITE { "white" } else { "black" }
+                    );
+                    (i / self.width, i % self.width)
+                } else if let Some(i) = self.pick_guess(&self.current) {
+                    // White is explored first, black is the alternative.
+                    let mut black = self.current.clone();
+                    black[i] = BLACK;
+                    let mut white = self.current.clone();
+                    white[i] = WHITE;
+
+                    self.stack.push(black);
+                    self.stack.push(white);
+                    self.guesses += 1;
+
+                    self.explenation = String::from("Rules can't proceed, guess white");
+                    (i / self.width, i % self.width)
+                } else {
+                    unreachable!("Grid that can't proceed has unknown cells.");
+                };
+
+                self.explenation = format!("{} at ({}, {}).", self.explenation, x, y);
+            }
+            _ => self.explenation = String::from("Contradiction, undo last guess."),
+        }
+
+        self.step
+    }
+
+    fn get_state(&self) -> Nurikabe {
+        let verbose = if self.verbose {
+            self.explenation.clone()
+        } else {
+            String::from("")
+        };
+
+        Nurikabe {
+            path: self.path.clone(),
+            width: self.width,
+            height: self.height,
+            solved: self.solution.is_some(),
+            iteration: self.iteration,
+            data: self.solution.clone().unwrap_or_else(|| self.current.clone()),
+            duration: 0,
+            seed: None,
+            verbose,
+        }
+    }
+
+    fn get_iteration(&self) -> usize {
+        self.iteration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::load_nurikabe;
+    use crate::nurikabe::tests::{data_puzzle, unsolvable_puzzle};
+
+    fn run(solver: &mut BacktrackSolver) -> Step {
+        loop {
+            let step = solver.solve();
+            if step != Step::Proceed && step != Step::CannotProceed {
+                return step;
+            }
+        }
+    }
+
+    #[test]
+    fn solves_with_probes() {
+        let puzzle = data_puzzle("nurikabe4.csv");
+        let mut solver = BacktrackSolver::new(puzzle.clone());
+
+        assert_eq!(run(&mut solver), Step::SolutionFound);
+        assert_eq!(verify(&puzzle, &solver.get_state()), vec![]);
+        assert!(solver.get_probes() > 0);
+        assert_eq!(solver.get_guesses(), 0);
+    }
+
+    #[test]
+    fn solves_with_guesses() {
+        // Generated expert puzzle, `nurikabe3.csv` also needs guesses but
+        // takes minutes in a debug build.
+        let puzzle =
+            load_nurikabe("0,0,0,0,0\n1,0,0,0,0\n0,0,6,0,0\n0,0,0,0,0\n0,0,0,0,0").unwrap();
+        let mut solver = BacktrackSolver::new(puzzle.clone());
+
+        assert_eq!(run(&mut solver), Step::SolutionFound);
+        assert_eq!(verify(&puzzle, &solver.get_state()), vec![]);
+        assert!(solver.get_guesses() > 0);
+    }
+
+    #[test]
+    fn unsolvable() {
+        let mut solver = BacktrackSolver::new(unsolvable_puzzle());
+
+        assert_eq!(run(&mut solver), Step::Contradiction);
+        assert!(!solver.get_state().solved);
+    }
+}
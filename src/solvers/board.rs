@@ -0,0 +1,274 @@
+use std::sync::Arc;
+
+use super::*;
+
+/// Set of cells, indexed row by row.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cells(Vec<u64>);
+
+impl Cells {
+    pub fn new(len: usize) -> Self {
+        Self(vec![0; len.div_ceil(64)])
+    }
+
+    #[inline]
+    pub fn insert(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    #[inline]
+    pub fn remove(&mut self, i: usize) {
+        self.0[i / 64] &= !(1 << (i % 64));
+    }
+
+    #[inline]
+    pub fn contains(&self, i: usize) -> bool {
+        (self.0[i / 64] >> (i % 64)) & 1 == 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&word| word == 0)
+    }
+
+    pub fn is_subset(&self, other: &Cells) -> bool {
+        self.0.iter().zip(other.0.iter()).all(|(a, b)| a & !b == 0)
+    }
+
+    pub fn intersect(&mut self, other: &Cells) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a &= b;
+        }
+    }
+
+    pub fn union(&mut self, other: &Cells) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a |= b;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(k, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(k * 64 + bit)
+            })
+        })
+    }
+}
+
+/// Neighbours of every cell, in the same order as the `for_valid_*` helpers.
+///
+#[derive(Debug)]
+struct Tables {
+    orthogonal: Vec<Vec<usize>>,
+    diagonal: Vec<Vec<usize>>,
+    surrounding: Vec<Vec<usize>>,
+}
+
+impl Tables {
+    fn new(width: usize, height: usize) -> Self {
+        let mut tables = Self {
+            orthogonal: vec![vec![]; width * height],
+            diagonal: vec![vec![]; width * height],
+            surrounding: vec![vec![]; width * height],
+        };
+
+        for i in 0..width * height {
+            let (x, y) = (i / width, i % width);
+
+            for_valid_neighbours(width, height, x, y, |a, b| {
+                tables.orthogonal[i].push(a * width + b)
+            });
+            for_valid_diagonal_neighbours(width, height, x, y, |a, b| {
+                tables.diagonal[i].push(a * width + b)
+            });
+            for_valid_neighbours_with_outside(width, height, x, y, |a, b| {
+                tables.surrounding[i].push(a * width + b)
+            });
+        }
+
+        tables
+    }
+}
+
+/// Grid of black, white and unknown cells shared by the solvers. Cells are
+/// flat indices, row by row. Neighbour tables are computed once and shared by
+/// every clone, so copying a board only copies its bitsets.
+///
+#[derive(Clone, Debug)]
+pub struct Board {
+    pub width: usize,
+    pub height: usize,
+    black: Cells,
+    white: Cells,
+    unknown: Cells,
+    tables: Arc<Tables>,
+}
+
+impl Board {
+    /// Board with all cells unknown.
+    ///
+    pub fn new(width: usize, height: usize) -> Self {
+        let size = width * height;
+        let mut unknown = Cells::new(size);
+        for i in 0..size {
+            unknown.insert(i);
+        }
+
+        Self {
+            width,
+            height,
+            black: Cells::new(size),
+            white: Cells::new(size),
+            unknown,
+            tables: Arc::new(Tables::new(width, height)),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        x * self.width + y
+    }
+
+    #[inline]
+    pub fn pos(&self, i: usize) -> (usize, usize) {
+        (i / self.width, i % self.width)
+    }
+
+    /// Colour of the cell, islands are `State::White`.
+    ///
+    #[inline]
+    pub fn get(&self, i: usize) -> State {
+        if self.black.contains(i) {
+            State::Black
+        } else if self.white.contains(i) {
+            State::White
+        } else {
+            State::Unknown
+        }
+    }
+
+    /// Sets the colour of the cell, islands are stored as white.
+    ///
+    pub fn set(&mut self, i: usize, state: State) {
+        self.black.remove(i);
+        self.white.remove(i);
+        self.unknown.remove(i);
+
+        match state {
+            State::Black => self.black.insert(i),
+            State::White | State::Island(_) => self.white.insert(i),
+            State::Unknown => self.unknown.insert(i),
+        }
+    }
+
+    #[inline]
+    pub fn is_black(&self, i: usize) -> bool {
+        self.black.contains(i)
+    }
+
+    #[inline]
+    pub fn is_white(&self, i: usize) -> bool {
+        self.white.contains(i)
+    }
+
+    #[inline]
+    pub fn is_unknown(&self, i: usize) -> bool {
+        self.unknown.contains(i)
+    }
+
+    pub fn black(&self) -> &Cells {
+        &self.black
+    }
+
+    pub fn white(&self) -> &Cells {
+        &self.white
+    }
+
+    pub fn unknown(&self) -> &Cells {
+        &self.unknown
+    }
+
+    /// Cells above, below, left and right of the cell.
+    ///
+    #[inline]
+    pub fn neighbours(&self, i: usize) -> &[usize] {
+        &self.tables.orthogonal[i]
+    }
+
+    /// Cells touching the cell by a corner.
+    ///
+    #[inline]
+    pub fn diagonal(&self, i: usize) -> &[usize] {
+        &self.tables.diagonal[i]
+    }
+
+    /// All eight cells around the cell, in order around it.
+    ///
+    #[inline]
+    pub fn surrounding(&self, i: usize) -> &[usize] {
+        &self.tables.surrounding[i]
+    }
+
+    /// Number of 2x2 blocks of black cells.
+    ///
+    pub fn num_pools(&self) -> usize {
+        let mut num = 0;
+
+        for x in 0..self.height.saturating_sub(1) {
+            for y in 0..self.width.saturating_sub(1) {
+                let i = self.index(x, y);
+                if self.is_black(i)
+                    && self.is_black(i + 1)
+                    && self.is_black(i + self.width)
+                    && self.is_black(i + self.width + 1)
+                {
+                    num += 1;
+                }
+            }
+        }
+
+        num
+    }
+
+    /// Cells connected to `start` through cells that pass `open`. `start` is
+    /// always included.
+    ///
+    pub fn flood(&self, start: usize, open: impl Fn(usize) -> bool) -> Cells {
+        let mut reached = Cells::new(self.len());
+        let mut stack = vec![start];
+        reached.insert(start);
+
+        while let Some(i) = stack.pop() {
+            for &j in self.neighbours(i) {
+                if !reached.contains(j) && open(j) {
+                    reached.insert(j);
+                    stack.push(j);
+                }
+            }
+        }
+
+        reached
+    }
+}
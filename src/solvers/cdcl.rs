@@ -0,0 +1,769 @@
+use std::ops::Not;
+
+/// Boolean variable of a `Cdcl` instance, numbered from 0.
+///
+pub type Var = usize;
+
+/// Variable or its negation.
+///
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn new(var: Var, positive: bool) -> Self {
+        Lit((var as u32) << 1 | !positive as u32)
+    }
+
+    /// Literal that is true when the variable is.
+    ///
+    #[inline]
+    pub fn positive(var: Var) -> Self {
+        Lit::new(var, true)
+    }
+
+    #[inline]
+    pub fn var(self) -> Var {
+        (self.0 >> 1) as Var
+    }
+
+    #[inline]
+    pub fn is_positive(self) -> bool {
+        self.0 & 1 == 0
+    }
+
+    #[inline]
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    #[inline]
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+/// Result of `Cdcl::solve`.
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// Every clause is satisfied by `Cdcl::model`.
+    Sat,
+    /// Clauses can't be satisfied.
+    Unsat,
+    /// Conflict limit reached, the search continues on the next call.
+    Unknown,
+}
+
+#[derive(Debug)]
+struct Clause {
+    lits: Vec<Lit>,
+    learnt: bool,
+    activity: f64,
+    deleted: bool,
+}
+
+/// Clause watching a literal, visited when the literal becomes false. The
+/// clause is satisfied when `blocker` is true.
+///
+#[derive(Clone, Copy, Debug)]
+struct Watcher {
+    clause: usize,
+    blocker: Lit,
+}
+
+/// Unassigned variables ordered by activity.
+///
+#[derive(Debug, Default)]
+struct Heap {
+    heap: Vec<Var>,
+    indices: Vec<Option<usize>>,
+}
+
+impl Heap {
+    fn contains(&self, var: Var) -> bool {
+        self.indices[var].is_some()
+    }
+
+    fn insert(&mut self, var: Var, activity: &[f64]) {
+        if self.contains(var) {
+            return;
+        }
+
+        self.indices[var] = Some(self.heap.len());
+        self.heap.push(var);
+        self.up(self.heap.len() - 1, activity);
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<Var> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.indices[top] = None;
+
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.indices[last] = Some(0);
+            self.down(0, activity);
+        }
+
+        Some(top)
+    }
+
+    /// Restores the order after the activity of `var` increased.
+    ///
+    fn increased(&mut self, var: Var, activity: &[f64]) {
+        if let Some(i) = self.indices[var] {
+            self.up(i, activity);
+        }
+    }
+
+    fn up(&mut self, mut i: usize, activity: &[f64]) {
+        let var = self.heap[i];
+
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.heap[parent]] >= activity[var] {
+                break;
+            }
+
+            self.heap[i] = self.heap[parent];
+            self.indices[self.heap[i]] = Some(i);
+            i = parent;
+        }
+
+        self.heap[i] = var;
+        self.indices[var] = Some(i);
+    }
+
+    fn down(&mut self, mut i: usize, activity: &[f64]) {
+        let var = self.heap[i];
+
+        loop {
+            let left = 2 * i + 1;
+            if left >= self.heap.len() {
+                break;
+            }
+
+            let right = left + 1;
+            let child = if right < self.heap.len()
+                && activity[self.heap[right]] > activity[self.heap[left]]
+            {
+                right
+            } else {
+                left
+            };
+
+            if activity[self.heap[child]] <= activity[var] {
+                break;
+            }
+
+            self.heap[i] = self.heap[child];
+            self.indices[self.heap[i]] = Some(i);
+            i = child;
+        }
+
+        self.heap[i] = var;
+        self.indices[var] = Some(i);
+    }
+}
+
+/// Conflict driven clause learning SAT solver, in the style of MiniSat: two
+/// watched literals, first UIP learning, VSIDS branching with saved phases,
+/// Luby restarts and removal of inactive learnt clauses.
+///
+/// Clauses can be added between calls of `solve`, learnt clauses are kept.
+///
+#[derive(Debug, Default)]
+pub struct Cdcl {
+    clauses: Vec<Clause>,
+    /// Watchers of every literal.
+    watches: Vec<Vec<Watcher>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    /// Start of every decision level in the trail.
+    trail_lim: Vec<usize>,
+    /// Next literal of the trail to propagate.
+    head: usize,
+    activity: Vec<f64>,
+    var_inc: f64,
+    clause_inc: f64,
+    heap: Heap,
+    phases: Vec<bool>,
+    seen: Vec<bool>,
+    model: Vec<bool>,
+    unsat: bool,
+    num_learnts: usize,
+    max_learnts: f64,
+    restarts: usize,
+    /// Conflicts left until the next restart.
+    restart_budget: usize,
+    pub conflicts: usize,
+}
+
+const VAR_DECAY: f64 = 0.95;
+const CLAUSE_DECAY: f64 = 0.999;
+const RESTART_BASE: usize = 100;
+
+impl Cdcl {
+    pub fn new() -> Self {
+        Self {
+            var_inc: 1.0,
+            clause_inc: 1.0,
+            restart_budget: RESTART_BASE,
+            ..Default::default()
+        }
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn new_var(&mut self) -> Var {
+        let var = self.values.len();
+
+        self.watches.push(vec![]);
+        self.watches.push(vec![]);
+        self.values.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.activity.push(0.0);
+        self.heap.indices.push(None);
+        self.heap.insert(var, &self.activity);
+        self.phases.push(false);
+        self.seen.push(false);
+
+        var
+    }
+
+    /// Adds a clause. Variables are created as needed.
+    ///
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        self.backtrack(0);
+
+        if let Some(max) = lits.iter().map(|lit| lit.var()).max() {
+            while self.num_vars() <= max {
+                self.new_var();
+            }
+        }
+
+        let mut lits = lits.to_vec();
+        lits.sort();
+        lits.dedup();
+
+        // Tautology or already satisfied.
+        if lits.windows(2).any(|pair| pair[0] == !pair[1])
+            || lits.iter().any(|&lit| self.value(lit) == Some(true))
+        {
+            return;
+        }
+
+        lits.retain(|&lit| self.value(lit).is_none());
+
+        match lits.len() {
+            0 => self.unsat = true,
+            1 => self.assign(lits[0], None),
+            _ => {
+                self.attach(Clause {
+                    lits,
+                    learnt: false,
+                    activity: 0.0,
+                    deleted: false,
+                });
+            }
+        }
+    }
+
+    /// Searches for a model for at most `max_conflicts` conflicts.
+    ///
+    pub fn solve(&mut self, max_conflicts: usize) -> Outcome {
+        if self.unsat {
+            return Outcome::Unsat;
+        }
+
+        if self.max_learnts == 0.0 {
+            self.max_learnts = (self.clauses.len() as f64 / 3.0).max(1000.0);
+        }
+
+        let start = self.conflicts;
+
+        loop {
+            if let Some(conflict) = self.propagate() {
+                self.conflicts += 1;
+
+                if self.trail_lim.is_empty() {
+                    self.unsat = true;
+                    return Outcome::Unsat;
+                }
+
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                self.learn(learnt);
+
+                self.var_inc /= VAR_DECAY;
+                self.clause_inc /= CLAUSE_DECAY;
+                self.restart_budget = self.restart_budget.saturating_sub(1);
+            } else {
+                if self.restart_budget == 0 {
+                    self.restarts += 1;
+                    self.restart_budget = RESTART_BASE * luby(self.restarts);
+                    self.backtrack(0);
+                }
+
+                if self.conflicts - start >= max_conflicts {
+                    self.backtrack(0);
+                    return Outcome::Unknown;
+                }
+
+                if self.num_learnts as f64 >= self.max_learnts + self.trail.len() as f64 {
+                    self.reduce();
+                    self.max_learnts *= 1.1;
+                }
+
+                match self.pick() {
+                    Some(lit) => {
+                        self.trail_lim.push(self.trail.len());
+                        self.assign(lit, None);
+                    }
+                    None => {
+                        self.model = self.values.iter().map(|v| v.unwrap()).collect();
+                        self.backtrack(0);
+                        return Outcome::Sat;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Values of the variables in the last model.
+    ///
+    pub fn model(&self) -> &[bool] {
+        &self.model
+    }
+
+    #[inline]
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.values[lit.var()].map(|v| v == lit.is_positive())
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.values[var] = Some(lit.is_positive());
+        self.levels[var] = self.trail_lim.len();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Adds the clause and watches its first two literals.
+    ///
+    fn attach(&mut self, clause: Clause) -> usize {
+        let index = self.clauses.len();
+        let (first, second) = (clause.lits[0], clause.lits[1]);
+
+        self.watches[first.index()].push(Watcher {
+            clause: index,
+            blocker: second,
+        });
+        self.watches[second.index()].push(Watcher {
+            clause: index,
+            blocker: first,
+        });
+
+        if clause.learnt {
+            self.num_learnts += 1;
+        }
+        self.clauses.push(clause);
+
+        index
+    }
+
+    /// Propagates the assignments of the trail. Returns the conflicting clause
+    /// if there is one.
+    ///
+    fn propagate(&mut self) -> Option<usize> {
+        while self.head < self.trail.len() {
+            let false_lit = !self.trail[self.head];
+            self.head += 1;
+
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut conflict = None;
+            let (mut i, mut j) = (0, 0);
+
+            while i < watchers.len() {
+                let watcher = watchers[i];
+                i += 1;
+
+                if self.value(watcher.blocker) == Some(true) {
+                    watchers[j] = watcher;
+                    j += 1;
+                    continue;
+                }
+
+                if self.clauses[watcher.clause].deleted {
+                    continue;
+                }
+
+                // Watched false literal goes to the second place.
+                let lits = &mut self.clauses[watcher.clause].lits;
+                if lits[0] == false_lit {
+                    lits.swap(0, 1);
+                }
+                let first = lits[0];
+                let kept = Watcher {
+                    clause: watcher.clause,
+                    blocker: first,
+                };
+
+                if first != watcher.blocker && self.value(first) == Some(true) {
+                    watchers[j] = kept;
+                    j += 1;
+                    continue;
+                }
+
+                let replacement = (2..self.clauses[watcher.clause].lits.len())
+                    .find(|&k| self.value(self.clauses[watcher.clause].lits[k]) != Some(false));
+
+                if let Some(k) = replacement {
+                    let lits = &mut self.clauses[watcher.clause].lits;
+                    lits.swap(1, k);
+                    self.watches[lits[1].index()].push(kept);
+                    continue;
+                }
+
+                watchers[j] = kept;
+                j += 1;
+
+                if self.value(first) == Some(false) {
+                    conflict = Some(watcher.clause);
+                    while i < watchers.len() {
+                        watchers[j] = watchers[i];
+                        i += 1;
+                        j += 1;
+                    }
+                } else {
+                    self.assign(first, Some(watcher.clause));
+                }
+            }
+
+            watchers.truncate(j);
+            self.watches[false_lit.index()] = watchers;
+
+            if conflict.is_some() {
+                self.head = self.trail.len();
+                return conflict;
+            }
+        }
+
+        None
+    }
+
+    /// Learns the first UIP clause of the conflict. Returns the clause, with
+    /// the asserting literal first, and the level to backtrack to.
+    ///
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let level = self.trail_lim.len();
+        let mut learnt = vec![];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut index = self.trail.len();
+        let mut skip = 0;
+
+        let uip = loop {
+            self.bump_clause(clause);
+
+            for k in skip..self.clauses[clause].lits.len() {
+                let lit = self.clauses[clause].lits[k];
+                let var = lit.var();
+
+                if !self.seen[var] && self.levels[var] > 0 {
+                    self.seen[var] = true;
+                    self.bump_var(var);
+
+                    if self.levels[var] == level {
+                        pending += 1;
+                    } else {
+                        learnt.push(lit);
+                    }
+                }
+            }
+
+            // Latest literal of the trail that is part of the conflict.
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            self.seen[lit.var()] = false;
+            pending -= 1;
+
+            if pending == 0 {
+                break lit;
+            }
+
+            clause = self.reasons[lit.var()].unwrap();
+            // Reason clauses have the implied literal first.
+            skip = 1;
+        };
+
+        // Literals implied by the other literals of the clause are redundant.
+        let minimized = learnt
+            .iter()
+            .copied()
+            .filter(|lit| match self.reasons[lit.var()] {
+                None => true,
+                Some(reason) => self.clauses[reason].lits[1..]
+                    .iter()
+                    .any(|other| !self.seen[other.var()] && self.levels[other.var()] > 0),
+            })
+            .collect::<Vec<_>>();
+
+        for lit in learnt.iter() {
+            self.seen[lit.var()] = false;
+        }
+
+        let mut learnt = vec![!uip];
+        learnt.extend(minimized);
+
+        // Literal of the highest level goes second, to be watched.
+        let mut back_level = 0;
+        for k in 1..learnt.len() {
+            let level = self.levels[learnt[k].var()];
+            if level > back_level {
+                back_level = level;
+                learnt.swap(1, k);
+            }
+        }
+
+        (learnt, back_level)
+    }
+
+    fn learn(&mut self, lits: Vec<Lit>) {
+        let asserting = lits[0];
+
+        if lits.len() == 1 {
+            self.assign(asserting, None);
+            return;
+        }
+
+        let index = self.attach(Clause {
+            lits,
+            learnt: true,
+            activity: 0.0,
+            deleted: false,
+        });
+        self.bump_clause(index);
+        self.assign(asserting, Some(index));
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.trail_lim.len() <= level {
+            return;
+        }
+
+        let start = self.trail_lim[level];
+        for k in (start..self.trail.len()).rev() {
+            let lit = self.trail[k];
+            let var = lit.var();
+            self.values[var] = None;
+            self.reasons[var] = None;
+            self.phases[var] = lit.is_positive();
+            self.heap.insert(var, &self.activity);
+        }
+
+        self.trail.truncate(start);
+        self.trail_lim.truncate(level);
+        self.head = self.trail.len();
+    }
+
+    /// Most active unassigned variable, with its saved phase.
+    ///
+    fn pick(&mut self) -> Option<Lit> {
+        while let Some(var) = self.heap.pop(&self.activity) {
+            if self.values[var].is_none() {
+                return Some(Lit::new(var, self.phases[var]));
+            }
+        }
+
+        None
+    }
+
+    fn bump_var(&mut self, var: Var) {
+        self.activity[var] += self.var_inc;
+
+        if self.activity[var] > 1e100 {
+            for activity in self.activity.iter_mut() {
+                *activity *= 1e-100;
+            }
+            self.var_inc *= 1e-100;
+        }
+
+        self.heap.increased(var, &self.activity);
+    }
+
+    fn bump_clause(&mut self, index: usize) {
+        let clause = &mut self.clauses[index];
+        if !clause.learnt {
+            return;
+        }
+
+        clause.activity += self.clause_inc;
+
+        if clause.activity > 1e20 {
+            for clause in self.clauses.iter_mut().filter(|clause| clause.learnt) {
+                clause.activity *= 1e-20;
+            }
+            self.clause_inc *= 1e-20;
+        }
+    }
+
+    /// Removes the less active half of the learnt clauses. Binary clauses and
+    /// reasons of current assignments are kept.
+    ///
+    fn reduce(&mut self) {
+        let mut candidates = (0..self.clauses.len())
+            .filter(|&index| {
+                let clause = &self.clauses[index];
+                clause.learnt
+                    && !clause.deleted
+                    && clause.lits.len() > 2
+                    && self.reasons[clause.lits[0].var()] != Some(index)
+            })
+            .collect::<Vec<_>>();
+
+        candidates.sort_by(|&a, &b| {
+            self.clauses[a]
+                .activity
+                .total_cmp(&self.clauses[b].activity)
+        });
+
+        for &index in candidates[..candidates.len() / 2].iter() {
+            let clause = &mut self.clauses[index];
+            clause.deleted = true;
+            clause.lits = vec![];
+            self.num_learnts -= 1;
+        }
+
+        for watchers in self.watches.iter_mut() {
+            watchers.retain(|watcher| !self.clauses[watcher.clause].deleted);
+        }
+    }
+}
+
+/// Luby sequence 1, 1, 2, 1, 1, 2, 4, ... starting at index 1.
+///
+fn luby(mut i: usize) -> usize {
+    loop {
+        let mut size = 1;
+        while size < i {
+            size = 2 * size + 1;
+        }
+
+        if size == i {
+            return size.div_ceil(2);
+        }
+
+        i -= size / 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solvers::rng::Rng;
+
+    fn satisfies(model: &[bool], clauses: &[Vec<Lit>]) -> bool {
+        clauses
+            .iter()
+            .all(|clause| clause.iter().any(|l| model[l.var()] == l.is_positive()))
+    }
+
+    fn solver(num_vars: usize, clauses: &[Vec<Lit>]) -> Cdcl {
+        let mut cdcl = Cdcl::new();
+        for _ in 0..num_vars {
+            cdcl.new_var();
+        }
+        for clause in clauses.iter() {
+            cdcl.add_clause(clause);
+        }
+        cdcl
+    }
+
+    #[test]
+    fn implication() {
+        let (a, b) = (Lit::positive(0), Lit::positive(1));
+        let clauses = vec![vec![a], vec![!a, b]];
+
+        let mut cdcl = solver(2, &clauses);
+        assert_eq!(cdcl.solve(usize::MAX), Outcome::Sat);
+        assert_eq!(cdcl.model(), &[true, true]);
+    }
+
+    #[test]
+    fn contradiction() {
+        let a = Lit::positive(0);
+
+        let mut cdcl = solver(1, &[vec![a], vec![!a]]);
+        assert_eq!(cdcl.solve(usize::MAX), Outcome::Unsat);
+    }
+
+    #[test]
+    fn pigeonhole() {
+        // 5 pigeons don't fit in 4 holes.
+        let (pigeons, holes) = (5, 4);
+        let var = |p: usize, h: usize| Lit::positive(p * holes + h);
+
+        let mut clauses = vec![];
+        for p in 0..pigeons {
+            clauses.push((0..holes).map(|h| var(p, h)).collect());
+        }
+        for h in 0..holes {
+            for p in 0..pigeons {
+                for q in p + 1..pigeons {
+                    clauses.push(vec![!var(p, h), !var(q, h)]);
+                }
+            }
+        }
+
+        let mut cdcl = solver(pigeons * holes, &clauses);
+        assert_eq!(cdcl.solve(usize::MAX), Outcome::Unsat);
+    }
+
+    #[test]
+    fn random_formulas_match_brute_force() {
+        let mut rng = Rng::new(7);
+        let num_vars = 12;
+
+        for _ in 0..200 {
+            let clauses = (0..50)
+                .map(|_| {
+                    (0..3)
+                        .map(|_| Lit::new(rng.random_int(0..num_vars), rng.random_float() < 0.5))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            let expected = (0..1usize << num_vars).any(|bits| {
+                let model = (0..num_vars)
+                    .map(|v| bits >> v & 1 == 1)
+                    .collect::<Vec<_>>();
+                satisfies(&model, &clauses)
+            });
+
+            let mut cdcl = solver(num_vars, &clauses);
+            match cdcl.solve(usize::MAX) {
+                Outcome::Sat => {
+                    assert!(expected);
+                    assert!(satisfies(cdcl.model(), &clauses));
+                }
+                Outcome::Unsat => assert!(!expected),
+                Outcome::Unknown => panic!("Solver gave up without a limit"),
+            }
+        }
+    }
+}
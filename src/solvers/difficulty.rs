@@ -0,0 +1,230 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::backtrack::BacktrackSolver;
+use super::naive::Deduction;
+use super::state::WHITE;
+use super::*;
+
+/// Score of a cell forced by probing both colours.
+const PROBE_WEIGHT: usize = 10;
+
+/// Score of a guess the backtracking search had to make.
+const GUESS_WEIGHT: usize = 50;
+
+/// Difficulty tier, given by the hardest technique a puzzle needs.
+///
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub enum Tier {
+    /// Solved with completed islands, single unknowns and bordering cells.
+    Easy,
+    /// Also needs two unknowns, potential pools, unreachable cells, river
+    /// connectivity or island expansion.
+    Medium,
+    /// Needs probing, trying a colour and finding a contradiction.
+    Hard,
+    /// Needs guessing.
+    Expert,
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Tier::Easy => "easy",
+            Tier::Medium => "medium",
+            Tier::Hard => "hard",
+            Tier::Expert => "expert",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Tier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Tier::Easy),
+            "medium" => Ok(Tier::Medium),
+            "hard" => Ok(Tier::Hard),
+            "expert" => Ok(Tier::Expert),
+            _ => Err(format!("Unknown difficulty: {}", s)),
+        }
+    }
+}
+
+/// Difficulty of a puzzle, as returned by `rate`. Higher score is harder,
+/// puzzles of the same tier can be sorted by it.
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rating {
+    pub score: usize,
+    pub tier: Tier,
+    /// Number of steps in which each rule marked cells.
+    pub rules: Vec<(Deduction, usize)>,
+    pub probes: usize,
+    pub guesses: usize,
+}
+
+impl Deduction {
+    /// Score of one step of the rule.
+    ///
+    fn weight(&self) -> usize {
+        match self {
+            Deduction::CompletedIsland | Deduction::SingleUnknown | Deduction::Bordering => 1,
+            Deduction::TwoUnknown | Deduction::PotentialPool => 2,
+            Deduction::Unreachable | Deduction::RiverArticulation | Deduction::SealedRiver => 3,
+            Deduction::IslandExpansion => 4,
+        }
+    }
+}
+
+/// Rates the difficulty of a puzzle. The puzzle is solved with the rules of
+/// `NaiveSolver` first, counting how often each one fires. When the rules get
+/// stuck, the backtracking search continues and its probes and guesses are
+/// counted as well.
+///
+pub fn rate(nurikabe: Nurikabe) -> Result<Rating, String> {
+    let mut solver = NaiveSolver::new(nurikabe.clone());
+    let mut rules = Deduction::ALL.map(|rule| (rule, 0));
+
+    let step = loop {
+        let step = solver.solve();
+
+        if let Some(rule) = solver.last_rule() {
+            if let Some((_, num)) = rules.iter_mut().find(|(r, _)| *r == rule) {
+                *num += 1;
+            }
+        }
+
+        if step != Step::Proceed {
+            break step;
+        }
+    };
+
+    let (probes, guesses) = match step {
+        Step::SolutionFound => (0, 0),
+        Step::CannotProceed => {
+            // Island cells are reported with the island size, only clues keep it.
+            let data = solver
+                .get_state()
+                .data
+                .into_iter()
+                .zip(nurikabe.data.iter())
+                .map(|(v, &clue)| if clue <= 0 && v > 0 { WHITE } else { v })
+                .collect();
+
+            let mut backtrack = BacktrackSolver::new(Nurikabe { data, ..nurikabe });
+            let step = loop {
+                let step = backtrack.solve();
+                if step != Step::Proceed {
+                    break step;
+                }
+            };
+
+            if step != Step::SolutionFound {
+                return Err(String::from("Puzzle has no solution"));
+            }
+
+            (backtrack.get_probes(), backtrack.get_guesses())
+        }
+        _ => return Err(String::from("Puzzle has no solution")),
+    };
+
+    let score = rules
+        .iter()
+        .map(|(rule, num)| rule.weight() * num)
+        .sum::<usize>()
+        + probes * PROBE_WEIGHT
+        + guesses * GUESS_WEIGHT;
+
+    let advanced = rules.iter().any(|&(rule, num)| {
+        num > 0
+            && matches!(
+                rule,
+                Deduction::TwoUnknown
+                    | Deduction::PotentialPool
+                    | Deduction::Unreachable
+                    | Deduction::RiverArticulation
+                    | Deduction::SealedRiver
+                    | Deduction::IslandExpansion
+            )
+    });
+
+    let tier = if guesses > 0 {
+        Tier::Expert
+    } else if probes > 0 {
+        Tier::Hard
+    } else if advanced {
+        Tier::Medium
+    } else {
+        Tier::Easy
+    };
+
+    Ok(Rating {
+        score,
+        tier,
+        rules: rules.to_vec(),
+        probes,
+        guesses,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::tests::{data_puzzle, grid, unsolvable_puzzle};
+
+    fn counts(rating: &Rating) -> Vec<usize> {
+        rating.rules.iter().map(|&(_, num)| num).collect()
+    }
+
+    #[test]
+    fn easy() {
+        let rating = rate(grid(&["2??", "???", "1?1"])).unwrap();
+
+        assert_eq!(rating.tier, Tier::Easy);
+        assert_eq!((rating.probes, rating.guesses), (0, 0));
+    }
+
+    #[test]
+    fn rules_only() {
+        let rating = rate(data_puzzle("nurikabe5x5.csv")).unwrap();
+
+        assert_eq!(rating.tier, Tier::Medium);
+        assert_eq!(counts(&rating), vec![3, 4, 0, 1, 2, 1, 0, 0, 0]);
+        assert_eq!((rating.probes, rating.guesses), (0, 0));
+        assert_eq!(rating.score, 15);
+    }
+
+    #[test]
+    fn probing() {
+        let rating = rate(data_puzzle("nurikabe4.csv")).unwrap();
+
+        assert_eq!(rating.tier, Tier::Hard);
+        assert_eq!(counts(&rating), vec![2, 2, 1, 1, 1, 1, 0, 0, 0]);
+        assert_eq!((rating.probes, rating.guesses), (8, 0));
+        assert_eq!(rating.score, 92);
+    }
+
+    #[test]
+    fn unsolvable() {
+        assert_eq!(
+            rate(unsolvable_puzzle()).unwrap_err(),
+            "Puzzle has no solution"
+        );
+    }
+
+    #[test]
+    fn tier_names() {
+        for tier in [Tier::Easy, Tier::Medium, Tier::Hard, Tier::Expert] {
+            assert_eq!(tier.to_string().parse::<Tier>(), Ok(tier));
+        }
+        assert_eq!(
+            "impossible".parse::<Tier>(),
+            Err(String::from("Unknown difficulty: impossible"))
+        );
+    }
+}
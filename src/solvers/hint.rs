@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+
+use super::naive::Deduction;
+use super::sat::SatSolver;
+use super::state::{BLACK, UNKNOWN, WHITE};
+use super::*;
+
+/// Next deduction for a player, as returned by `hint`.
+///
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Hint {
+    /// Rule that makes the deduction. `None` when the rules can't proceed and
+    /// the cell is taken from the solution.
+    pub rule: Option<Deduction>,
+    pub explenation: String,
+    /// Cells to mark white, as (row, column).
+    pub white: Vec<(usize, usize)>,
+    /// Cells to mark black, as (row, column).
+    pub black: Vec<(usize, usize)>,
+    /// Cells the player marked with the wrong colour. They are ignored when
+    /// looking for the deduction.
+    pub mistakes: Vec<(usize, usize)>,
+}
+
+/// Solution of a puzzle, kept to give any number of hints on it without
+/// solving it again.
+///
+pub struct Hints {
+    puzzle: Nurikabe,
+    solution: Vec<i32>,
+}
+
+impl Hints {
+    /// Solves the puzzle with the SAT solver. When the puzzle has more than one
+    /// solution, hints follow the first one found.
+    ///
+    pub fn new(puzzle: Nurikabe) -> Result<Self, String> {
+        let mut solver = SatSolver::new(puzzle.clone());
+
+        loop {
+            match solver.solve() {
+                Step::Proceed => (),
+                Step::SolutionFound => break,
+                _ => return Err(String::from("Puzzle has no solution")),
+            }
+        }
+
+        Ok(Self {
+            puzzle,
+            solution: solver.get_state().data,
+        })
+    }
+
+    /// Tells if the hints are for the puzzle, same size and the same clues.
+    ///
+    pub fn is_for(&self, puzzle: &Nurikabe) -> bool {
+        let clues =
+            |nurikabe: &Nurikabe| nurikabe.data.iter().map(|&v| v.max(0)).collect::<Vec<_>>();
+
+        self.puzzle.width == puzzle.width
+            && self.puzzle.height == puzzle.height
+            && clues(&self.puzzle) == clues(puzzle)
+    }
+
+    /// Finds the next single deduction for a partially filled player grid. The
+    /// player grid uses the same values as the solvers: `-1` black, `-2` white
+    /// and `-3` unknown. Clues are taken from the puzzle.
+    ///
+    /// Marks of the player that differ from the solution are reported as
+    /// mistakes and ignored when looking for the deduction.
+    ///
+    pub fn hint(&self, player: &Nurikabe) -> Result<Hint, String> {
+        let puzzle = &self.puzzle;
+        let solution = &self.solution;
+        let (width, height) = (puzzle.width, puzzle.height);
+
+        if player.width != width || player.height != height || player.data.len() != width * height {
+            return Err(format!(
+                "Player grid is {} x {}, puzzle is {} x {}",
+                player.width, player.height, width, height
+            ));
+        }
+
+        let cell = |i: usize| (i / width, i % width);
+
+        let mut grid = vec![UNKNOWN; width * height];
+        let mut mistakes = vec![];
+
+        for (i, (&clue, &v)) in puzzle.data.iter().zip(player.data.iter()).enumerate() {
+            if clue > 0 {
+                // Clues stay, marking one black is a mistake.
+                grid[i] = clue;
+                if v == BLACK {
+                    mistakes.push(cell(i));
+                }
+                continue;
+            }
+
+            let mark = match v {
+                BLACK => BLACK,
+                WHITE | 1.. => WHITE,
+                _ => continue,
+            };
+
+            if (mark == BLACK) == (solution[i] == BLACK) {
+                grid[i] = mark;
+            } else {
+                mistakes.push(cell(i));
+            }
+        }
+
+        let mut hint = Hint {
+            mistakes,
+            ..Default::default()
+        };
+
+        let mut solver = NaiveSolver::new(Nurikabe::new(width, height, grid.clone()));
+        solver.verbose = true;
+
+        match solver.solve() {
+            Step::SolutionFound => hint.explenation = String::from("Puzzle solved!"),
+            Step::Proceed => {
+                let after = solver.get_state().data;
+
+                for (i, (&before, &after)) in grid.iter().zip(after.iter()).enumerate() {
+                    match (before, after) {
+                        (UNKNOWN, BLACK) => hint.black.push(cell(i)),
+                        (UNKNOWN, UNKNOWN) => (),
+                        (UNKNOWN, _) => hint.white.push(cell(i)),
+                        _ => (),
+                    }
+                }
+
+                hint.rule = solver.last_rule();
+                hint.explenation = solver.explenation.clone();
+            }
+            _ => {
+                // Rules are stuck, an unknown cell next to a known one is revealed.
+                let i = (0..grid.len())
+                    .filter(|&i| grid[i] == UNKNOWN)
+                    .find(|&i| {
+                        let mut next_to_known = false;
+                        for_valid_neighbours(width, height, i / width, i % width, |a, b| {
+                            next_to_known |= grid[a * width + b] != UNKNOWN;
+                        });
+                        next_to_known
+                    })
+                    .or_else(|| grid.iter().position(|&v| v == UNKNOWN))
+                    .ok_or_else(|| String::from("Puzzle has no solution"))?;
+
+                if solution[i] == BLACK {
+                    hint.black.push(cell(i));
+                } else {
+                    hint.white.push(cell(i));
+                }
+
+                hint.explenation =
+                    String::from("Rules can't proceed, cell taken from the solution.");
+            }
+        }
+
+        Ok(hint)
+    }
+}
+
+/// Next deduction for the player grid, see `Hints::hint`. Solves the puzzle
+/// on every call, keep `Hints` to give more hints on the same puzzle.
+///
+pub fn hint(puzzle: &Nurikabe, player: &Nurikabe) -> Result<Hint, String> {
+    Hints::new(puzzle.clone())?.hint(player)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::tests::grid;
+
+    /// Has the single solution `2.#/###/1#1`.
+    ///
+    fn hints() -> Hints {
+        Hints::new(grid(&["2??", "???", "1?1"])).unwrap()
+    }
+
+    #[test]
+    fn flags_mistakes() {
+        // Clue and a white cell marked black.
+        let hint = hints().hint(&grid(&["##?", "???", "1?1"])).unwrap();
+
+        assert_eq!(hint.mistakes, vec![(0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn rule_deduction() {
+        let hint = hints().hint(&grid(&["2??", "???", "1?1"])).unwrap();
+
+        assert_eq!(hint.rule, Some(Deduction::CompletedIsland));
+        assert_eq!(hint.black, vec![(1, 0), (1, 2), (2, 1)]);
+        assert_eq!(hint.white, vec![]);
+        assert_eq!(hint.mistakes, vec![]);
+    }
+
+    #[test]
+    fn taken_from_solution() {
+        // Rules can't tell on which side the island grows.
+        let hints = Hints::new(grid(&["?2?"])).unwrap();
+        let hint = hints.hint(&grid(&["?2?"])).unwrap();
+
+        assert_eq!(hint.rule, None);
+        assert!(hint.explenation.contains("taken from the solution"));
+        if hints.solution[0] == BLACK {
+            assert_eq!((hint.white, hint.black), (vec![], vec![(0, 0)]));
+        } else {
+            assert_eq!((hint.white, hint.black), (vec![(0, 0)], vec![]));
+        }
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let error = hints().hint(&grid(&["2?", "??"])).unwrap_err();
+
+        assert_eq!(error, "Player grid is 2 x 2, puzzle is 3 x 3");
+    }
+
+    #[test]
+    fn is_for() {
+        let hints = hints();
+
+        assert!(hints.is_for(&grid(&["2.#", "###", "1#1"])));
+        assert!(!hints.is_for(&grid(&["2??", "???", "1?2"])));
+        assert!(!hints.is_for(&grid(&["2??", "???"])));
+    }
+}
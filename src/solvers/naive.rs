@@ -1,43 +1,45 @@
-use std::{
-    cell::{Ref, RefCell},
-    collections::{BTreeSet, HashSet, VecDeque},
-    rc::Rc,
-};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
-use wasm_bindgen::JsValue;
+use serde::{Deserialize, Serialize};
 
+use super::board::{Board, Cells};
+use super::state::{BLACK, WHITE};
 use super::*;
 
+/// Partial shapes explored for one island by `solve_island_expansion`, before
+/// it falls back to a distance bound.
+const MAX_SHAPES: usize = 500;
+
+/// Connected cells of the same colour. `main` is the root of the region in
+/// the disjoint-set forest of `NaiveSolver`, unknown cells are regions of
+/// their own.
+///
 #[derive(Debug)]
 struct Region {
     main: (usize, usize),
     state: State,
-    known: BTreeSet<(usize, usize)>,
+    known: Vec<(usize, usize)>,
+    /// Unknown cells next to the region, its liberties.
     unknown: BTreeSet<(usize, usize)>,
 }
 
 impl Region {
-    fn unknown(x: usize, y: usize) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Self {
+    fn unknown(x: usize, y: usize) -> Self {
+        Self {
             main: (x, y),
             state: State::Unknown,
-            known: BTreeSet::new(),
+            known: vec![],
             unknown: BTreeSet::new(),
-        }))
+        }
     }
 
-    fn new(
-        x: usize,
-        y: usize,
-        state: State,
-        unknown: BTreeSet<(usize, usize)>,
-    ) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Self {
+    fn new(x: usize, y: usize, state: State, unknown: BTreeSet<(usize, usize)>) -> Self {
+        Self {
             main: (x, y),
             state,
-            known: BTreeSet::from_iter([(x, y)]),
+            known: vec![(x, y)],
             unknown,
-        }))
+        }
     }
 
     #[inline]
@@ -71,41 +73,99 @@ impl Region {
 
     fn remove_unknown(&mut self, x: usize, y: usize) {
         self.unknown.remove(&(x, y));
-        // if let Some(index) = self
-        //     .unknown
-        //     .iter()
-        //     .position(move |&(a, b)| x == a && y == b)
-        // {
-        //     self.unknown.remove(index);
-        // }
     }
 }
 
+/// Deduction rules of `NaiveSolver`, in the order they are tried.
+///
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum Deduction {
+    CompletedIsland,
+    SingleUnknown,
+    TwoUnknown,
+    Bordering,
+    PotentialPool,
+    Unreachable,
+    RiverArticulation,
+    SealedRiver,
+    IslandExpansion,
+}
+
+impl Deduction {
+    pub const ALL: [Deduction; 9] = [
+        Deduction::CompletedIsland,
+        Deduction::SingleUnknown,
+        Deduction::TwoUnknown,
+        Deduction::Bordering,
+        Deduction::PotentialPool,
+        Deduction::Unreachable,
+        Deduction::RiverArticulation,
+        Deduction::SealedRiver,
+        Deduction::IslandExpansion,
+    ];
+}
+
+/// One iteration of `NaiveSolver`, recorded when `trace` is enabled.
+///
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub iteration: usize,
+    pub step: Step,
+    /// Rule that marked the cells, `None` if no rule made progress.
+    pub rule: Option<Deduction>,
+    /// Cells marked white, as (row, column).
+    pub white: Vec<(usize, usize)>,
+    /// Cells marked black, as (row, column).
+    pub black: Vec<(usize, usize)>,
+    /// Why the grid can't be solved, for `Step::Contradiction`.
+    pub contradiction: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct NaiveSolver {
 	path: String,
     width: usize,
     height: usize,
-    grid: Vec<Vec<Rc<RefCell<Region>>>>,
-    regions: Vec<Rc<RefCell<Region>>>,
+    board: Board,
+    /// Parent of every cell in the disjoint-set forest of regions, by flat
+    /// index. Roots are their own parent.
+    parent: Vec<usize>,
+    /// Region of every root, entries of other cells are left over.
+    regions: Vec<Region>,
+    /// Roots of the known regions.
+    roots: Cells,
     num_black_cells: usize,
     step: Step,
     solved: bool,
     iteration: usize,
+    /// Rule that marked cells in the last step.
+    rule: Option<Deduction>,
+    /// Cells marked in the last step, kept for the trace.
+    marked_white: Vec<(usize, usize)>,
+    marked_black: Vec<(usize, usize)>,
+    /// Reason of the contradiction, once one is found.
+    reason: Option<&'static str>,
+    events: Vec<Event>,
     pub explenation: String,
     pub verbose: bool,
+    /// Records every iteration as an `Event`.
+    pub trace: bool,
 }
 
 impl NaiveSolver {
+    /// Cells already marked black (-1) or white (-2) in the data are kept, so
+    /// a partially solved grid can be continued.
+    ///
     pub fn new(nurikabe: Nurikabe) -> Self {
         let width = nurikabe.width;
         let height = nurikabe.height;
 
         let mut num_black_cells = width * height;
 
-        let mut grid = vec![];
-        let mut row = Vec::with_capacity(width);
-        let mut regions = vec![];
+        let mut board = Board::new(width, height);
+        let mut regions = Vec::with_capacity(width * height);
+        let mut roots = Cells::new(width * height);
+        let mut marked = vec![];
 
         for (i, val) in nurikabe.data.into_iter().enumerate() {
             let state = State::new(val);
@@ -113,96 +173,126 @@ impl NaiveSolver {
             let x = i / width;
             let y = i % width;
 
+            match val {
+                BLACK => marked.push((x, y, State::Black)),
+                WHITE => marked.push((x, y, State::White)),
+                _ => (),
+            }
+
             match state {
                 State::Island(size) => {
                     num_black_cells -= size as usize;
 
-                    let mut unknowns = BTreeSet::new();
-                    for_valid_neighbours(width, height, x, y, |a, b| {
-                        unknowns.insert((a, b));
-                    });
+                    let unknowns = board.neighbours(i).iter().map(|&j| board.pos(j)).collect();
 
-                    let region = Region::new(x, y, state, unknowns);
-                    row.push(region.clone());
-                    regions.push(region.clone());
+                    board.set(i, state);
+                    roots.insert(i);
+                    regions.push(Region::new(x, y, state, unknowns));
                 }
                 _ => {
-                    row.push(Region::unknown(x, y));
+                    regions.push(Region::unknown(x, y));
                 }
             };
-
-            if i % width == width - 1 {
-                grid.push(row);
-                row = Vec::with_capacity(width);
-            }
         }
 
-        Self {
+        let mut solver = Self {
 			path: nurikabe.path,
             width,
             height,
-            grid,
+            parent: (0..width * height).collect(),
+            board,
             regions,
+            roots,
             num_black_cells,
             step: Step::Proceed,
             solved: false,
             explenation: String::from(""),
             verbose: false,
+            trace: false,
             iteration: 0,
+            rule: None,
+            marked_white: vec![],
+            marked_black: vec![],
+            reason: None,
+            events: vec![],
+        };
+
+        for (x, y, state) in marked {
+            solver.mark(x, y, state);
         }
+
+        solver
     }
 
-    fn add_region(&mut self, state: State, x: usize, y: usize) {
-        let mut unknowns = BTreeSet::new();
-        for_valid_neighbours(self.width, self.height, x, y, |a, b| {
-            if self.sample(a, b).is_unknow() {
-                unknowns.insert((a, b));
-            }
-        });
+    /// Rule that made progress in the last call of `solve`, if any.
+    ///
+    pub fn last_rule(&self) -> Option<Deduction> {
+        self.rule
+    }
 
-        let region = Region::new(x, y, state, unknowns);
+    /// Recorded iterations, empty unless `trace` is enabled.
+    ///
+    pub fn get_trace(&self) -> &[Event] {
+        &self.events
+    }
 
-        self.grid[x][y] = region.clone();
-        self.regions.push(region.clone());
+    fn add_region(&mut self, state: State, x: usize, y: usize) {
+        let i = self.board.index(x, y);
+        let unknowns = self
+            .board
+            .neighbours(i)
+            .iter()
+            .filter(|&&j| self.board.is_unknown(j))
+            .map(|&j| self.board.pos(j))
+            .collect();
+
+        self.board.set(i, state);
+        self.regions[i] = Region::new(x, y, state, unknowns);
+        self.roots.insert(i);
     }
 
-    /// Returns total number of known cells.
+    /// Root of the region of the cell. Regions are joined by size, so the
+    /// trees stay shallow.
     ///
-    fn known(&self) -> usize {
-        let mut num = 0;
-
-        for x in 0..self.height {
-            for y in 0..self.width {
-                if self.sample(x, y).is_known() {
-                    num += 1;
-                }
-            }
+    #[inline]
+    fn find(&self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            i = self.parent[i];
         }
+        i
+    }
 
-        num
+    /// Known regions.
+    ///
+    fn regions(&self) -> impl Iterator<Item = &Region> + '_ {
+        self.roots.iter().map(|i| &self.regions[i])
     }
 
-    #[inline]
-    fn sample(&self, x: usize, y: usize) -> Ref<Region> {
-        (*self.grid[x][y]).borrow()
+    /// Returns total number of known cells.
+    ///
+    fn known(&self) -> usize {
+        self.board.len() - self.board.unknown().len()
     }
 
     #[inline]
-    fn sample_reg(&self, a: (usize, usize)) -> Rc<RefCell<Region>> {
-        self.grid[a.0][a.1].clone()
+    fn sample(&self, x: usize, y: usize) -> &Region {
+        &self.regions[self.find(self.board.index(x, y))]
     }
 
+    /// Calls `f` with the position of every neighbour of the cell.
+    ///
     #[inline]
-    fn sample_value(&self, x: usize, y: usize) -> i32 {
-        (*self.grid[x][y]).borrow().state.into()
+    fn for_neighbours(&self, x: usize, y: usize, mut f: impl FnMut(usize, usize)) {
+        for &j in self.board.neighbours(self.board.index(x, y)) {
+            let (a, b) = self.board.pos(j);
+            f(a, b);
+        }
     }
 
     /// Check if white region can be connected to some island.
     ///
     fn is_white_region_to_big(&self, size: usize) -> bool {
-        for region in self.regions.iter() {
-            let region = region.as_ref().borrow();
-
+        for region in self.regions() {
             if let State::Island(max_size) = region.state {
                 let island_size = region.size();
                 if (island_size + size + 1) <= max_size as usize {
@@ -218,42 +308,44 @@ impl NaiveSolver {
         self.step = Step::Contradiction;
 
         // Check if there is a pool
-        for x in 0..self.height - 1 {
-            for y in 0..self.width - 1 {
-                if self.grid[x][y].as_ref().borrow().is_black()
-                    && self.grid[x + 1][y].as_ref().borrow().is_black()
-                    && self.grid[x][y + 1].as_ref().borrow().is_black()
-                    && self.grid[x + 1][y + 1].as_ref().borrow().is_black()
-                {
-                    // console_log!("Contradiction: Pool detected!");
-                    return true;
-                }
-            }
+        if self.board.num_pools() > 0 {
+            self.reason = Some("Pool of black cells.");
+            return true;
+        }
+
+        if !self.is_river_connectable() {
+            self.reason = Some("Black cells can't be connected in one river.");
+            return true;
         }
 
         // Check if white and black cells don't match
         let mut num_black = 0;
         let mut num_white = 0;
 
-        for region in self.regions.iter() {
-            let region = region.as_ref().borrow();
+        for i in self.roots.iter() {
+            let region = &self.regions[i];
 
             match region.state {
                 State::Island(size) => {
                     if region.size() > size as usize {
-                        // console_log!("Contradiction: Island with to many white cells.");
+                        self.reason = Some("Island with too many white cells.");
                         return true;
                     }
-                }
-                State::White => {
-                    // Region is marked white but not an island. Can they still connect?
-                    if self.is_white_region_to_big(region.size()) {
-                        // console_log!(
-                        //     "Contradiction: White region that can't be connected to any remaining island."
-                        // );
+
+                    if region.size() < size as usize && region.unknown.is_empty() {
+                        self.reason = Some("Island is enclosed before it's complete.");
                         return true;
                     }
                 }
+                // Region is marked white but not an island. Can they still connect?
+                State::White
+                    if region.unknown.is_empty()
+                        || self.is_white_region_to_big(region.size()) =>
+                {
+                    self.reason =
+                        Some("White region that can't be connected to any remaining island.");
+                    return true;
+                }
                 _ => (),
             }
 
@@ -265,12 +357,12 @@ impl NaiveSolver {
         }
 
         if num_black > self.num_black_cells {
-            // console_log!("Contradiction: To many black cells.");
+            self.reason = Some("Too many black cells.");
             return true;
         }
 
         if num_white > (self.width * self.height) - self.num_black_cells {
-            // console_log!("Contradiction: To many white cells.");
+            self.reason = Some("Too many white cells.");
             return true;
         }
 
@@ -281,8 +373,8 @@ impl NaiveSolver {
     fn solve_completed_islands(&mut self) -> bool {
         let mut mark_black = BTreeSet::new();
 
-        for region in self.regions.iter() {
-            let mut region = region.borrow_mut();
+        for i in self.roots.iter() {
+            let region = &mut self.regions[i];
 
             if let State::Island(size) = region.state {
                 if size as usize == region.size() {
@@ -291,15 +383,20 @@ impl NaiveSolver {
             }
         }
 
-        self.update_grid(BTreeSet::new(), mark_black, "Complete island found.")
+        self.update_grid(
+            BTreeSet::new(),
+            mark_black,
+            Deduction::CompletedIsland,
+            "Complete island found.",
+        )
     }
 
     fn solve_single_unknown(&mut self) -> bool {
         let mut mark_white = BTreeSet::new();
         let mut mark_black = BTreeSet::new();
 
-        for region in self.regions.iter() {
-            let mut region = region.borrow_mut();
+        for i in self.roots.iter() {
+            let region = &mut self.regions[i];
 
             if region.unknown.len() != 1 {
                 continue;
@@ -316,52 +413,108 @@ impl NaiveSolver {
             }
         }
 
-        self.update_grid(mark_white, mark_black, "Found single uknown.")
+        self.update_grid(
+            mark_white,
+            mark_black,
+            Deduction::SingleUnknown,
+            "Found single uknown.",
+        )
     }
 
-    // TODO
+    /// Island that needs one more cell can be finished through any of its
+    /// valid liberties. Cells that turn black for every choice are marked
+    /// black, e.g. the corner shared by two diagonal liberties. With a single
+    /// valid liberty, it is marked white.
+    ///
     fn solve_two_unknown(&mut self) -> bool {
-        // let mut mark_black = BTreeSet::new();
-
-        // for region in self.regions.iter() {
-        //     let mut region = region.borrow_mut();
+        let mut mark_white = BTreeSet::new();
+        let mut mark_black = BTreeSet::new();
 
-        //     if region.unknown.len() != 2 {
-        //         continue;
-        //     }
-        // }
+        for region in self.regions() {
+            let State::Island(size) = region.state else {
+                continue;
+            };
 
-        // self.update_grid(
-        //     BTreeSet::new(),
-        //     mark_black,
-        //     "Found two uknown for region with size N - 1.",
-        // )
-        false
-    }
+            if region.size() + 1 != size as usize || region.unknown.len() < 2 {
+                continue;
+            }
 
-    fn solve_bordering(&mut self) -> bool {
-        let mut mark_black = BTreeSet::new();
+            // Black cells for each way to finish the island.
+            let mut shared: Option<BTreeSet<(usize, usize)>> = None;
+            let mut valid = vec![];
+
+            for &(x, y) in region.unknown.iter() {
+                let mut black = region.unknown.clone();
+                black.remove(&(x, y));
+
+                let mut is_valid = true;
+                self.for_neighbours(x, y, |a, b| {
+                    let r = self.sample(a, b);
+                    if r.is_unknow() {
+                        black.insert((a, b));
+                    } else if r.is_white() && r.main != region.main {
+                        is_valid = false;
+                    }
+                });
 
-        for x in 0..self.height {
-            for y in 0..self.width {
-                if !self.grid[x][y].as_ref().borrow().is_unknow() {
+                if !is_valid {
                     continue;
                 }
 
-                let mut island_count: usize = 0;
-                for_valid_neighbours(self.width, self.height, x, y, |a, b| {
-                    if self.sample(a, b).is_island() {
-                        island_count += 1;
-                    }
+                valid.push((x, y));
+                shared = Some(match shared {
+                    Some(shared) => shared.intersection(&black).copied().collect(),
+                    None => black,
                 });
+            }
+
+            if let [cell] = valid[..] {
+                mark_white.insert(cell);
+            } else if let Some(mut shared) = shared {
+                mark_black.append(&mut shared);
+            }
+        }
 
-                if island_count >= 2 {
-                    mark_black.insert((x, y));
+        // Cell can't be both, the contradiction is found by the next steps.
+        let both = mark_white.intersection(&mark_black).copied().collect::<Vec<_>>();
+        for cell in both {
+            mark_black.remove(&cell);
+        }
+
+        self.update_grid(
+            mark_white,
+            mark_black,
+            Deduction::TwoUnknown,
+            "Island needs one more cell, all ways to finish it share a black cell.",
+        )
+    }
+
+    fn solve_bordering(&mut self) -> bool {
+        let mut mark_black = BTreeSet::new();
+
+        for i in self.board.unknown().iter() {
+            let (x, y) = self.board.pos(i);
+
+            // Count distinct islands, two neighbours can be part of the same one.
+            let mut islands = BTreeSet::new();
+            self.for_neighbours(x, y, |a, b| {
+                let r = self.sample(a, b);
+                if r.is_island() {
+                    islands.insert(r.main);
                 }
+            });
+
+            if islands.len() >= 2 {
+                mark_black.insert((x, y));
             }
         }
 
-        self.update_grid(BTreeSet::new(), mark_black, "Found bordering islands.")
+        self.update_grid(
+            BTreeSet::new(),
+            mark_black,
+            Deduction::Bordering,
+            "Found bordering islands.",
+        )
     }
 
     fn solve_potential_pools(&mut self) -> bool {
@@ -378,7 +531,7 @@ impl NaiveSolver {
                     self.sample(x + 1, y + 1),
                 ];
 
-                list.sort_by(|a, b| a.state.cmp(&b.state));
+                list.sort_by_key(|a| a.state);
 
                 let mut black = 0;
                 let mut unknown = 0;
@@ -401,21 +554,247 @@ impl NaiveSolver {
             }
         }
 
-        self.update_grid(mark_white, BTreeSet::new(), "Found potential pool.")
+        self.update_grid(
+            mark_white,
+            BTreeSet::new(),
+            Deduction::PotentialPool,
+            "Found potential pool.",
+        )
     }
 
     fn solve_unrechable(&mut self) -> bool {
         let mut mark_black = BTreeSet::new();
 
-        for x in 0..self.height {
-            for y in 0..self.width {
-                if self.unreachable(x, y) {
-                    mark_black.insert((x, y));
+        // No island can grow further than its size.
+        let max_dist = self
+            .regions()
+            .filter_map(|r| match r.state {
+                State::Island(size) => Some(size),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+
+        for i in self.board.unknown().iter() {
+            let (x, y) = self.board.pos(i);
+            if self.unreachable(x, y, max_dist) {
+                mark_black.insert((x, y));
+            }
+        }
+
+        self.update_grid(
+            BTreeSet::new(),
+            mark_black,
+            Deduction::Unreachable,
+            "Solve unreachable.",
+        )
+    }
+
+    /// Checks if all black cells can still be connected through unknown
+    /// cells.
+    ///
+    fn is_river_connectable(&self) -> bool {
+        let Some(start) = self.board.black().iter().next() else {
+            return true;
+        };
+
+        let reached = self.board.flood(start, |j| !self.board.is_white(j));
+
+        self.board.black().is_subset(&reached)
+    }
+
+    /// Unknown cells that are the only link between black cells have to be
+    /// black. They are articulation points of the graph of black and unknown
+    /// cells, that cut off a part with black cells.
+    ///
+    /// ref: https://en.wikipedia.org/wiki/Biconnected_component
+    ///
+    fn solve_river_articulation(&mut self) -> bool {
+        let board = &self.board;
+        let size = board.len();
+
+        let Some(root) = board.black().iter().next() else {
+            return false;
+        };
+
+        // Iterative depth first search, with the number of black cells in the
+        // subtree of every cell.
+        let mut disc = vec![usize::MAX; size];
+        let mut low = vec![0; size];
+        let mut black = vec![0; size];
+        let mut cut = vec![false; size];
+        let mut time = 1;
+
+        disc[root] = 0;
+        black[root] = 1;
+        let mut stack = vec![(root, 0)];
+
+        while let Some(&(v, k)) = stack.last() {
+            if let Some(&u) = board.neighbours(v).get(k) {
+                stack.last_mut().unwrap().1 += 1;
+
+                if board.is_white(u) {
+                    continue;
                 }
+
+                if disc[u] == usize::MAX {
+                    disc[u] = time;
+                    low[u] = time;
+                    time += 1;
+                    black[u] = usize::from(board.is_black(u));
+                    stack.push((u, 0));
+                } else {
+                    low[v] = low[v].min(disc[u]);
+                }
+                continue;
             }
+
+            stack.pop();
+            if let Some(&(p, _)) = stack.last() {
+                low[p] = low[p].min(low[v]);
+                black[p] += black[v];
+
+                // Root is black, so the part below `v` is cut off from it.
+                if p != root && low[v] >= disc[p] && black[v] > 0 {
+                    cut[p] = true;
+                }
+            }
+        }
+
+        // Black cells out of reach are left to `contradictions`.
+        if board.black().iter().any(|i| disc[i] == usize::MAX) {
+            return false;
         }
 
-        self.update_grid(BTreeSet::new(), mark_black, "Solve unreachable.")
+        let mark_black = board
+            .unknown()
+            .iter()
+            .filter(|&i| cut[i])
+            .map(|i| board.pos(i))
+            .collect();
+
+        self.update_grid(
+            BTreeSet::new(),
+            mark_black,
+            Deduction::RiverArticulation,
+            "Cell is the only link between black cells.",
+        )
+    }
+
+    /// Unknown cell is white when, as a black cell, it would seal off a part
+    /// of the river. This happens when it has no unknown neighbours and the
+    /// black regions next to it have no other unknown neighbours.
+    ///
+    fn solve_sealed_river(&mut self) -> bool {
+        let mut mark_white = BTreeSet::new();
+
+        for i in self.board.unknown().iter() {
+            let (x, y) = self.board.pos(i);
+
+            let mut sealed = true;
+            let mut regions = BTreeSet::new();
+            let mut num_black = 1;
+
+            self.for_neighbours(x, y, |a, b| {
+                let r = self.sample(a, b);
+                if r.is_unknow() {
+                    sealed = false;
+                } else if r.is_black() && regions.insert(r.main) {
+                    num_black += r.size();
+                    sealed &= r.unknown.iter().all(|&cell| cell == (x, y));
+                }
+            });
+
+            if sealed && num_black < self.num_black_cells {
+                mark_white.insert((x, y));
+            }
+        }
+
+        self.update_grid(
+            mark_white,
+            BTreeSet::new(),
+            Deduction::SealedRiver,
+            "Black cell would seal off part of the river.",
+        )
+    }
+
+    /// Enumerates every shape each incomplete island can still grow into.
+    /// Cells covered by every shape of an island are white, cells bordering
+    /// every shape are black and cells no shape of any island reaches are
+    /// black. Islands with too many shapes are bounded by their distance.
+    ///
+    fn solve_island_expansion(&mut self) -> bool {
+        let expansion = Expansion::new(self);
+        let mut reach = Cells::new(self.width * self.height);
+
+        let mut mark_white = BTreeSet::new();
+        let mut mark_black = BTreeSet::new();
+
+        for region in self.regions() {
+            let State::Island(size) = region.state else {
+                continue;
+            };
+
+            if region.size() >= size as usize {
+                continue;
+            }
+
+            let mut start = Cells::new(self.width * self.height);
+            for &(x, y) in region.known.iter() {
+                start.insert(x * self.width + y);
+            }
+
+            let Some(shapes) = expansion.shapes(&start, region.main, size as usize) else {
+                reach.union(&expansion.bound(&start, size as usize));
+                continue;
+            };
+
+            let Some((first, rest)) = shapes.split_first() else {
+                // No way to finish the island, contradictions will find it.
+                continue;
+            };
+
+            let mut covered = first.clone();
+            let mut border = expansion.border(first);
+
+            for shape in rest {
+                covered.intersect(shape);
+                border.intersect(&expansion.border(shape));
+            }
+
+            for shape in shapes.iter() {
+                reach.union(shape);
+            }
+
+            // Shapes also cover the white regions they connect to.
+            mark_white.extend(
+                covered
+                    .iter()
+                    .filter(|&i| expansion.states[i] == State::Unknown)
+                    .map(|i| (i / self.width, i % self.width)),
+            );
+            mark_black.extend(border.iter().map(|i| (i / self.width, i % self.width)));
+        }
+
+        for (i, &state) in expansion.states.iter().enumerate() {
+            if state == State::Unknown && !reach.contains(i) {
+                mark_black.insert((i / self.width, i % self.width));
+            }
+        }
+
+        // Cell can't be both, the contradiction is found by the next steps.
+        let both = mark_white.intersection(&mark_black).copied().collect::<Vec<_>>();
+        for cell in both {
+            mark_white.remove(&cell);
+            mark_black.remove(&cell);
+        }
+
+        self.update_grid(
+            mark_white,
+            mark_black,
+            Deduction::IslandExpansion,
+            "Island expansion, every way to finish the islands agrees.",
+        )
     }
 
     /// Breath first search(BFS) to first viable island. Island is viable only
@@ -423,47 +802,56 @@ impl NaiveSolver {
     ///
     /// ref: https://www.redblobgames.com/pathfinding/a-star/introduction.html
     ///
-    fn unreachable(&self, x: usize, y: usize) -> bool {
+    fn unreachable(&self, x: usize, y: usize, max_dist: i32) -> bool {
         if self.sample(x, y).is_known() {
             return false;
         }
 
         let mut frontier = VecDeque::new();
         let mut reached = HashSet::new();
-        const MAX_DIST: i32 = 15;
 
         frontier.push_back((x, y, 1));
         reached.insert((x, y));
 
         while let Some((x, y, cur_dist)) = frontier.pop_front() {
-            if cur_dist > MAX_DIST {
+            if cur_dist > max_dist {
                 continue;
             }
 
+            // Regions are identified by their main cell, since two neighbours
+            // can belong to the same region.
             let mut white_regions = BTreeSet::new();
             let mut islands = BTreeSet::new();
 
-            for_valid_neighbours(self.width, self.height, x, y, |a, b| {
+            self.for_neighbours(x, y, |a, b| {
                 let r = self.sample(a, b);
                 match r.state {
-                    State::White => white_regions.insert((a, b)),
-                    State::Island(_) => islands.insert((a, b)),
+                    State::White => white_regions.insert(r.main),
+                    State::Island(_) => islands.insert(r.main),
                     _ => false,
                 };
             });
 
+            if islands.len() > 1 {
+                continue;
+            }
+
             let mut cur_size = 0;
 
             for &(x, y) in white_regions.iter() {
                 cur_size += self.sample(x, y).size();
             }
 
-            for &(x, y) in islands.iter() {
-                cur_size += self.sample(x, y).size();
-            }
-
-            if islands.len() > 1 {
-                continue;
+            // Path joins the island together with neighbouring white regions.
+            if let Some(&pos) = islands.first() {
+                let r = self.sample(pos.0, pos.1);
+                if let State::Island(size) = r.state {
+                    if cur_dist as usize + cur_size + r.size() <= size as usize {
+                        return false;
+                    } else {
+                        continue;
+                    }
+                }
             }
 
             if !white_regions.is_empty() {
@@ -474,27 +862,14 @@ impl NaiveSolver {
                 }
             }
 
-            // if size - r.known.len() as i32 >= cur_dist {
-            if islands.len() == 1 {
-                let pos = *islands.first().unwrap();
-                let r = self.sample(pos.0, pos.1);
-                if let State::Island(size) = r.state {
-                    if cur_dist + cur_size as i32 <= size {
-                        return false;
-                    } else {
-                        continue;
-                    }
-                }
-            }
-
-            for_valid_neighbours(self.width, self.height, x, y, |a, b| {
+            self.for_neighbours(x, y, |a, b| {
                 let r = self.sample(a, b);
 
                 // Add unkown neighbours to queue if not already known.
 
                 match r.state {
                     State::Unknown if !reached.contains(&(a, b)) => {
-                        frontier.push_front((a, b, cur_dist + 1));
+                        frontier.push_back((a, b, cur_dist + 1));
                         reached.insert((a, b));
                     }
                     _ => (),
@@ -509,12 +884,19 @@ impl NaiveSolver {
         &mut self,
         mark_white: BTreeSet<(usize, usize)>,
         mark_black: BTreeSet<(usize, usize)>,
+        rule: Deduction,
         explenation: &str,
     ) -> bool {
         if mark_white.is_empty() && mark_black.is_empty() {
             return false;
         }
 
+        self.rule = Some(rule);
+        if self.trace {
+            self.marked_white = mark_white.iter().copied().collect();
+            self.marked_black = mark_black.iter().copied().collect();
+        }
+
         for (x, y) in mark_white {
             self.mark(x, y, State::White);
         }
@@ -535,87 +917,83 @@ impl NaiveSolver {
         match state {
             State::Black | State::White => {
                 if self.sample(x, y).is_known() {
-                    // console_log!("Contradiction: can only mark unknown cells.");
+                    self.reason = Some("Cell is marked twice.");
                     self.step = Step::Contradiction;
                     return;
                 }
 
-                for region in self.regions.iter_mut() {
-                    region.borrow_mut().remove_unknown(x, y);
+                // Only regions next to the cell have it as a liberty.
+                let i = self.board.index(x, y);
+                for k in 0..self.board.neighbours(i).len() {
+                    let root = self.find(self.board.neighbours(i)[k]);
+                    self.regions[root].remove_unknown(x, y);
                 }
 
                 self.add_region(state, x, y);
 
                 // Fuse neighbouring regions.
 
-                for_valid_neighbours(self.width, self.height, x, y, |a, b| {
-                    self.fuse_region((x, y), (a, b))
-                });
+                for k in 0..self.board.neighbours(i).len() {
+                    self.fuse_region(i, self.board.neighbours(i)[k]);
+                }
             }
             _ => (), // console_log!("Mark: Logical error, must be white or black"),
         }
     }
 
-    fn fuse_region(&mut self, a: (usize, usize), b: (usize, usize)) {
-        {
-            let main_region = self.sample_reg(a);
-            let region = self.sample_reg(b);
-
-            if Rc::ptr_eq(&main_region, &region) {
-                return;
-            }
-
-            let mut main_region = main_region.borrow_mut();
-            let mut region = region.borrow_mut();
-
-            // if region.is_unknow() {
-            //     return;
-            // }
-
-            if main_region.is_island() && region.is_island() {
-                self.step = Step::Contradiction;
-                return;
-            }
+    /// Joins the regions of two neighbouring cells, if they have the same
+    /// colour. The smaller region is added to the bigger one.
+    ///
+    fn fuse_region(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
 
-            if main_region.is_black() != region.is_black() {
-                return;
-            }
+        let (main_region, region) = (&self.regions[a], &self.regions[b]);
 
-            // Replace data
+        if region.is_unknow() || main_region.is_unknow() {
+            return;
+        }
 
-            if region.is_island() {
-                main_region.state = region.state;
-            };
+        if main_region.is_island() && region.is_island() {
+            self.reason = Some("Two islands are connected.");
+            self.step = Step::Contradiction;
+            return;
+        }
 
-            main_region.unknown.append(&mut region.unknown);
-            main_region.known.append(&mut region.known);
+        if main_region.is_black() != region.is_black() {
+            return;
         }
 
-        // Replace pointer to point at the same region.
+        let (root, child) = if main_region.size() >= region.size() {
+            (a, b)
+        } else {
+            (b, a)
+        };
 
-        let main = self.sample_reg(a);
+        let (x, y) = self.board.pos(child);
+        let mut region = std::mem::replace(&mut self.regions[child], Region::unknown(x, y));
+        let main_region = &mut self.regions[root];
 
-        for &(x, y) in main.as_ref().borrow().known.iter() {
-            self.grid[x][y] = main.clone();
+        if region.is_island() {
+            main_region.state = region.state;
         }
 
-        // Remove previous region from all region list.
-
-        let pointer = self.sample_reg(b);
+        main_region.known.append(&mut region.known);
 
-        if let Some(index) = self.regions.iter().position(|r| Rc::ptr_eq(r, &pointer)) {
-            self.regions.remove(index);
+        if main_region.unknown.len() < region.unknown.len() {
+            std::mem::swap(&mut main_region.unknown, &mut region.unknown);
         }
+        main_region.unknown.extend(region.unknown);
 
-        if !self.regions.iter().any(|r| Rc::ptr_eq(r, &main)) {
-            self.regions.push(main);
-        }
+        self.parent[child] = root;
+        self.roots.remove(child);
     }
 }
 
-impl Solver for NaiveSolver {
-    fn solve(&mut self) -> Step {
-        self.iteration += 1;
+impl NaiveSolver {
+    fn solve_step(&mut self) -> Step {
 
         if self.known() == self.width * self.height {
             if self.contradictions() {
@@ -627,6 +1005,7 @@ impl Solver for NaiveSolver {
                 self.explenation = format!("Known: {}/{}", self.known(), self.width * self.height);
             }
 
+            self.solved = true;
             return Step::SolutionFound;
         }
 
@@ -637,6 +1016,9 @@ impl Solver for NaiveSolver {
             || self.solve_potential_pools()
             || self.contradictions()
             || self.solve_unrechable()
+            || self.solve_river_articulation()
+            || self.solve_sealed_river()
+            || self.solve_island_expansion()
         {
             return self.step;
         }
@@ -650,35 +1032,429 @@ impl Solver for NaiveSolver {
 
         Step::CannotProceed
     }
+}
 
-    fn get_state(&self) -> JsValue {
-        let mut data = Vec::with_capacity(self.width * self.height);
-        for x in 0..self.height {
-            for y in 0..self.width {
-                data.push(self.sample_value(x, y));
-            }
+impl Solver for NaiveSolver {
+    fn solve(&mut self) -> Step {
+        // Marked cells given to `new` can already contradict each other.
+        if self.step == Step::Contradiction {
+            return self.step;
+        }
+
+        self.iteration += 1;
+        self.rule = None;
+
+        let step = self.solve_step();
+
+        if self.trace {
+            self.events.push(Event {
+                iteration: self.iteration,
+                step,
+                rule: self.rule,
+                white: std::mem::take(&mut self.marked_white),
+                black: std::mem::take(&mut self.marked_black),
+                contradiction: (step == Step::Contradiction)
+                    .then(|| String::from(self.reason.unwrap_or("Contradiction."))),
+            });
         }
 
+        step
+    }
+
+    fn get_state(&self) -> Nurikabe {
+        let data = (0..self.board.len())
+            .map(|i| self.regions[self.find(i)].state.into())
+            .collect();
+
         let verbose = if self.verbose {
             self.explenation.clone()
         } else {
             String::from("")
         };
 
-        serde_wasm_bindgen::to_value(&Nurikabe {
+        Nurikabe {
 			path: self.path.clone(),
             width: self.width,
             height: self.height,
             solved: self.solved,
             iteration: self.iteration,
 			duration: 0,
+            seed: None,
             data,
             verbose,
-        })
-        .unwrap()
+        }
     }
 
     fn get_iteration(&self) -> usize {
         self.iteration
     }
 }
+
+/// Snapshot of the grid used by `solve_island_expansion`, so shapes can be
+/// enumerated without going through the regions.
+///
+struct Expansion {
+    board: Board,
+    states: Vec<State>,
+    mains: Vec<(usize, usize)>,
+    /// Cells of white regions without a clue, by their main cell.
+    white: BTreeMap<(usize, usize), Vec<usize>>,
+}
+
+impl Expansion {
+    fn new(solver: &NaiveSolver) -> Self {
+        let mut states = vec![];
+        let mut mains = vec![];
+        let mut white = BTreeMap::<_, Vec<_>>::new();
+
+        for i in 0..solver.board.len() {
+            let r = &solver.regions[solver.find(i)];
+            if r.state == State::White {
+                white.entry(r.main).or_default().push(states.len());
+            }
+            states.push(r.state);
+            mains.push(r.main);
+        }
+
+        Self {
+            board: solver.board.clone(),
+            states,
+            mains,
+            white,
+        }
+    }
+
+    fn for_neighbours(&self, i: usize, mut f: impl FnMut(usize)) {
+        for &j in self.board.neighbours(i) {
+            f(j);
+        }
+    }
+
+    /// All shapes of `size` cells the island can grow into. Returns `None`
+    /// when there are more than `MAX_SHAPES` partial shapes.
+    ///
+    fn shapes(&self, start: &Cells, main: (usize, usize), size: usize) -> Option<Vec<Cells>> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![start.clone()];
+        let mut shapes = vec![];
+
+        while let Some(shape) = stack.pop() {
+            if shape.len() == size {
+                shapes.push(shape);
+                continue;
+            }
+
+            for i in self.border(&shape).iter() {
+                let Some(next) = self.grow(&shape, i, main, size) else {
+                    continue;
+                };
+
+                if seen.insert(next.clone()) {
+                    if seen.len() > MAX_SHAPES {
+                        return None;
+                    }
+                    stack.push(next);
+                }
+            }
+        }
+
+        Some(shapes)
+    }
+
+    /// Adds an unknown cell to the shape, together with the white regions it
+    /// connects to. Returns `None` if the cell touches another island or the
+    /// shape gets bigger than the island.
+    ///
+    fn grow(&self, shape: &Cells, i: usize, main: (usize, usize), size: usize) -> Option<Cells> {
+        let mut next = shape.clone();
+        next.insert(i);
+
+        let mut touches_island = false;
+        self.for_neighbours(i, |j| match self.states[j] {
+            State::Island(_) if self.mains[j] != main => touches_island = true,
+            State::White if !next.contains(j) => {
+                for &k in self.white[&self.mains[j]].iter() {
+                    next.insert(k);
+                }
+            }
+            _ => (),
+        });
+
+        (!touches_island && next.len() <= size).then_some(next)
+    }
+
+    /// Unknown cells next to the shape.
+    ///
+    fn border(&self, shape: &Cells) -> Cells {
+        let mut border = Cells::new(self.states.len());
+
+        for i in shape.iter() {
+            self.for_neighbours(i, |j| {
+                if self.states[j] == State::Unknown && !shape.contains(j) {
+                    border.insert(j);
+                }
+            });
+        }
+
+        border
+    }
+
+    /// Cells the island can reach with its missing cells, counting every
+    /// cell on the way. Used when there are too many shapes to enumerate.
+    ///
+    fn bound(&self, start: &Cells, size: usize) -> Cells {
+        let mut reached = start.clone();
+        let mut frontier = start.iter().map(|i| (i, start.len())).collect::<VecDeque<_>>();
+
+        while let Some((i, dist)) = frontier.pop_front() {
+            if dist >= size {
+                continue;
+            }
+
+            self.for_neighbours(i, |j| {
+                if self.states[j] != State::Black && !reached.contains(j) {
+                    reached.insert(j);
+                    frontier.push_back((j, dist + 1));
+                }
+            });
+        }
+
+        reached
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::tests::{data_puzzles, grid};
+
+    /// Runs one rule on the grid, given as rows of `grid`. Returns the rows
+    /// after the rule, or `None` when it marked nothing.
+    ///
+    fn after(rows: &[&str], rule: fn(&mut NaiveSolver) -> bool) -> Option<Vec<String>> {
+        let mut solver = NaiveSolver::new(grid(rows));
+        if !rule(&mut solver) {
+            return None;
+        }
+
+        let board = &solver.board;
+        let rows = rows
+            .iter()
+            .enumerate()
+            .map(|(x, row)| {
+                row.chars()
+                    .enumerate()
+                    .map(|(y, c)| match board.get(board.index(x, y)) {
+                        _ if c.is_ascii_digit() => c,
+                        State::Black => '#',
+                        State::Unknown => '?',
+                        _ => '.',
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Some(rows)
+    }
+
+    #[test]
+    fn two_unknown_diagonal() {
+        let rows = after(&["2??", "???", "???"], NaiveSolver::solve_two_unknown);
+
+        assert_eq!(rows.unwrap(), ["2??", "?#?", "???"]);
+    }
+
+    #[test]
+    fn two_unknown_single_valid() {
+        // Other liberty would join the island with the 1.
+        let rows = after(&["2??", "???", "1??"], NaiveSolver::solve_two_unknown);
+
+        assert_eq!(rows.unwrap(), ["2.?", "???", "1??"]);
+    }
+
+    #[test]
+    fn two_unknown_no_shared_cell() {
+        let rule = NaiveSolver::solve_two_unknown;
+
+        assert_eq!(after(&["???", "?2?", "???"], rule), None);
+        // Single liberty, left to `solve_single_unknown`.
+        assert_eq!(after(&["2#", "??"], rule), None);
+    }
+
+    #[test]
+    fn island_expansion_covered() {
+        // Both shapes of the 3 cover the cell to its right.
+        let rows = after(&["?3??", "####"], NaiveSolver::solve_island_expansion);
+
+        assert_eq!(rows.unwrap(), ["?3.?", "####"]);
+    }
+
+    #[test]
+    fn island_expansion_border() {
+        // Every shape of each island borders one cell in the middle.
+        let rows = after(&["2??", "??2"], NaiveSolver::solve_island_expansion);
+
+        assert_eq!(rows.unwrap(), ["2#?", "?#2"]);
+    }
+
+    #[test]
+    fn island_expansion_unreachable() {
+        let rows = after(&["?2??"], NaiveSolver::solve_island_expansion);
+
+        assert_eq!(rows.unwrap(), ["?2?#"]);
+    }
+
+    #[test]
+    fn island_expansion_shapes() {
+        let solver = NaiveSolver::new(grid(&["?3??", "####"]));
+        let expansion = Expansion::new(&solver);
+
+        let mut start = Cells::new(8);
+        start.insert(1);
+        let mut shapes = expansion
+            .shapes(&start, (0, 1), 3)
+            .unwrap()
+            .iter()
+            .map(|shape| shape.iter().collect())
+            .collect::<Vec<Vec<_>>>();
+        shapes.sort();
+
+        assert_eq!(shapes, vec![vec![0, 1, 2], vec![1, 2, 3]]);
+    }
+
+    #[test]
+    fn island_expansion_bound() {
+        // Too many shapes for a 9 in an empty grid, cells too far for it are
+        // still black.
+        let mut rows = vec![String::from("9????????")];
+        rows.extend((1..9).map(|_| String::from("?????????")));
+        let rows = rows.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let solver = NaiveSolver::new(grid(&rows));
+        let mut start = Cells::new(81);
+        start.insert(0);
+        assert!(Expansion::new(&solver).shapes(&start, (0, 0), 9).is_none());
+
+        let after = after(&rows, NaiveSolver::solve_island_expansion).unwrap();
+        for (x, row) in after.iter().enumerate() {
+            for (y, c) in row.chars().enumerate().skip(usize::from(x == 0)) {
+                assert_eq!(c, if x + y >= 9 { '#' } else { '?' }, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn river_articulation() {
+        // Top middle cell is the only link between the black cells, the one
+        // below it links nothing.
+        let rows = after(&["#?#", ".?."], NaiveSolver::solve_river_articulation);
+
+        assert_eq!(rows.unwrap(), ["###", ".?."]);
+    }
+
+    #[test]
+    fn river_articulation_cycle() {
+        // Black cells are linked both ways around the white cell.
+        let rule = NaiveSolver::solve_river_articulation;
+
+        assert_eq!(after(&["#??", "?.?", "??#"], rule), None);
+    }
+
+    #[test]
+    fn sealed_river() {
+        // As a black cell it would close off the black cells on the left.
+        let rows = after(&["#?2", "#.."], NaiveSolver::solve_sealed_river);
+
+        assert_eq!(rows.unwrap(), ["#.2", "#.."]);
+    }
+
+    #[test]
+    fn sealed_river_last_black() {
+        // All black cells would be in one river.
+        let rows = after(&["#?2", "##."], NaiveSolver::solve_sealed_river);
+
+        assert_eq!(rows, None);
+    }
+
+    #[test]
+    fn river_contradiction() {
+        let mut solver = NaiveSolver::new(grid(&["#1#"]));
+
+        assert!(!solver.is_river_connectable());
+        assert!(solver.contradictions());
+        let reason = solver.reason.unwrap();
+        assert_eq!(reason, "Black cells can't be connected in one river.");
+
+        // Connected through the unknown cells.
+        let solver = NaiveSolver::new(grid(&["#1#", "???"]));
+        assert!(solver.is_river_connectable());
+    }
+
+    /// Checks every region of the disjoint-set forest against a flood fill of
+    /// the board.
+    ///
+    fn check_regions(solver: &NaiveSolver, clues: &[i32]) {
+        let board = &solver.board;
+        let mut roots = vec![];
+
+        for i in 0..board.len() {
+            let root = solver.find(i);
+            let region = &solver.regions[root];
+            let state = board.get(i);
+
+            if state == State::Unknown {
+                assert!(region.is_unknow(), "{:?}", board.pos(i));
+                continue;
+            }
+
+            let cells = board.flood(i, |j| board.get(j) == state);
+            let flood = cells.iter().collect::<Vec<_>>();
+            let mut known = region
+                .known
+                .iter()
+                .map(|&(x, y)| board.index(x, y))
+                .collect::<Vec<_>>();
+            known.sort();
+            assert_eq!(known, flood, "{:?}", board.pos(i));
+
+            let mut unknown = BTreeSet::new();
+            for j in cells.iter() {
+                for &k in board.neighbours(j) {
+                    if board.is_unknown(k) {
+                        unknown.insert(board.pos(k));
+                    }
+                }
+            }
+            assert_eq!(region.unknown, unknown, "{:?}", board.pos(i));
+
+            match cells.iter().find(|&j| clues[j] > 0) {
+                Some(j) => assert_eq!(region.state, State::Island(clues[j])),
+                None => assert_eq!(region.state, state),
+            }
+
+            roots.push(root);
+        }
+
+        roots.sort();
+        roots.dedup();
+        assert_eq!(solver.roots.iter().collect::<Vec<_>>(), roots);
+    }
+
+    #[test]
+    fn regions_match_flood_fill() {
+        for (name, puzzle) in data_puzzles() {
+            let clues = puzzle.data.clone();
+            let mut solver = NaiveSolver::new(puzzle);
+
+            loop {
+                let step = solver.solve();
+                check_regions(&solver, &clues);
+
+                if step != Step::Proceed {
+                    assert_ne!(step, Step::Contradiction, "{}", name);
+                    break;
+                }
+            }
+        }
+    }
+}
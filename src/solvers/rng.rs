@@ -0,0 +1,131 @@
+use std::ops::Range;
+
+/// Small seedable pseudo random generator (xoshiro256**), so ant runs can be
+/// replayed bit for bit from the same seed on every platform.
+///
+/// ref: https://prng.di.unimi.it/
+///
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Expand the seed with SplitMix64, as recommended by the authors.
+        let mut seed = seed;
+        let mut state = [0; 4];
+        for s in state.iter_mut() {
+            seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *s = z ^ (z >> 31);
+        }
+
+        Self { state }
+    }
+
+    /// New seed from the system source of randomness. Seeds are kept to 32
+    /// bits, so they are easy to copy and fit in a JS number.
+    ///
+    pub fn random_seed() -> u64 {
+        let mut buf = [0u8; 4];
+        getrandom::getrandom(&mut buf).expect("Random");
+        u32::from_le_bytes(buf) as u64
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+
+    /// Random integer in range `start..end`. Range must not be empty.
+    ///
+    pub fn random_int(&mut self, range: Range<usize>) -> usize {
+        let dist = (range.end - range.start) as u128;
+        range.start + ((self.next_u64() as u128 * dist) >> 64) as usize
+    }
+
+    /// Random float in range `0.0..1.0`.
+    ///
+    pub fn random_float(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::tests::data_puzzle;
+    use crate::solvers::{aco::AntSolver, random_ant::RandomAntSolver, Solver};
+
+    #[test]
+    fn random_int_in_range() {
+        let mut rng = Rng::new(1);
+        let mut seen = [false; 5];
+
+        for _ in 0..1000 {
+            let n = rng.random_int(3..8);
+            assert!((3..8).contains(&n), "{}", n);
+            seen[n - 3] = true;
+        }
+        assert_eq!(seen, [true; 5]);
+        assert_eq!(rng.random_int(4..5), 4);
+    }
+
+    #[test]
+    fn random_float_in_range() {
+        let mut rng = Rng::new(1);
+        assert!((0..1000)
+            .map(|_| rng.random_float())
+            .all(|f| (0.0..1.0).contains(&f)));
+    }
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+
+        let a = (0..10).map(|_| a.next_u64()).collect::<Vec<_>>();
+        assert_eq!(a, (0..10).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(a, (0..10).map(|_| c.next_u64()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn same_seed_same_ant_run() {
+        let nurikabe = data_puzzle("nurikabe10x10v1.csv");
+        let ant = || AntSolver::new(10, 0.1, 0.1, 0.5, 0.9, 0.001, 7, nurikabe.clone());
+        let random_ant = || RandomAntSolver::new(10, 7, nurikabe.clone());
+
+        let mut solvers: [Box<dyn Solver>; 4] = [
+            Box::new(ant()),
+            Box::new(ant()),
+            Box::new(random_ant()),
+            Box::new(random_ant()),
+        ];
+        for _ in 0..3 {
+            for solver in solvers.iter_mut() {
+                solver.solve();
+            }
+        }
+
+        let states = solvers
+            .iter()
+            .map(|solver| solver.get_state())
+            .collect::<Vec<_>>();
+        assert_eq!(states[0].data, states[1].data);
+        assert_eq!(states[2].data, states[3].data);
+        assert_eq!(states[0].seed, Some(7));
+    }
+}
@@ -0,0 +1,627 @@
+use std::collections::VecDeque;
+
+use super::board::{Board, Cells};
+use super::cdcl::{Cdcl, Lit, Outcome, Var};
+use super::state::{BLACK, UNKNOWN, WHITE};
+use super::*;
+
+/// Conflicts per iteration, so a long search can be stopped in between.
+///
+const CONFLICTS: usize = 10_000;
+
+/// Puzzle as boolean clauses. Variable `i` is the colour of cell `i` (true is
+/// black). Every white cell belongs to one island, a white neighbour of an
+/// island cell is in the same island and islands have the size of their clue.
+/// No 2x2 black blocks and every black cell has a black neighbour.
+///
+/// Connectivity isn't encoded, `cuts` returns clauses that forbid the
+/// disconnected islands and river of a model.
+///
+#[derive(Debug)]
+pub struct Encoding {
+    pub width: usize,
+    pub height: usize,
+    pub num_vars: usize,
+    pub clauses: Vec<Vec<Lit>>,
+    board: Board,
+    /// Clue cell and size of every island.
+    islands: Vec<(usize, usize)>,
+    /// Island variables of every cell, as (island, variable).
+    members: Vec<Vec<(usize, Var)>>,
+}
+
+impl Encoding {
+    pub fn new(nurikabe: &Nurikabe) -> Self {
+        let board = Board::new(nurikabe.width, nurikabe.height);
+
+        let islands = nurikabe
+            .data
+            .iter()
+            .enumerate()
+            .filter(|(_, &v)| v > 0)
+            .map(|(i, &v)| (i, v as usize))
+            .collect::<Vec<_>>();
+
+        let mut encoding = Self {
+            width: nurikabe.width,
+            height: nurikabe.height,
+            num_vars: board.len(),
+            clauses: vec![],
+            members: vec![vec![]; board.len()],
+            board,
+            islands,
+        };
+
+        encoding.add_members();
+        encoding.add_cells();
+        encoding.add_pools();
+        encoding.add_sizes();
+
+        encoding
+    }
+
+    #[inline]
+    pub fn black(&self, i: usize) -> Lit {
+        Lit::positive(i)
+    }
+
+    /// Variable of cell `i` being part of `island`, if it can be.
+    ///
+    pub fn member(&self, i: usize, island: usize) -> Option<Var> {
+        self.members[i]
+            .iter()
+            .find(|&&(k, _)| k == island)
+            .map(|&(_, var)| var)
+    }
+
+    /// Island variables of cell `i`, as (island, variable).
+    ///
+    pub fn members(&self, i: usize) -> &[(usize, Var)] {
+        &self.members[i]
+    }
+
+    /// Clue cell and size of every island.
+    ///
+    pub fn islands(&self) -> &[(usize, usize)] {
+        &self.islands
+    }
+
+    /// Adds clauses that keep the islands and the river connected, so every
+    /// model is a solution without `cuts`. For solvers that can't add clauses
+    /// while searching. Island cells are reached from their clue and black
+    /// cells from the first black cell, one step at a time.
+    ///
+    pub fn connect(&mut self) {
+        let len = self.board.len();
+
+        for k in 0..self.islands.len() {
+            let (clue, size) = self.islands[k];
+
+            // Cells reached from the clue in at most `t` steps.
+            let mut reached = vec![vec![None; size]; len];
+            let cells = (0..len)
+                .filter(|&i| self.member(i, k).is_some())
+                .collect::<Vec<_>>();
+            for &i in cells.iter() {
+                for (t, var) in reached[i].iter_mut().enumerate() {
+                    if t > 0 || i == clue {
+                        *var = Some(self.new_var());
+                    }
+                }
+            }
+
+            for var in reached[clue].iter() {
+                self.clauses.push(vec![Lit::positive(var.unwrap())]);
+            }
+
+            for i in (0..len).filter(|&i| i != clue) {
+                let Some(member) = self.member(i, k).map(Lit::positive) else {
+                    continue;
+                };
+
+                for t in 1..size {
+                    let lit = Lit::positive(reached[i][t].unwrap());
+                    self.clauses.push(vec![!lit, member]);
+
+                    let mut clause = vec![!lit];
+                    clause.extend(reached[i][t - 1].map(Lit::positive));
+                    clause.extend(
+                        self.board
+                            .neighbours(i)
+                            .iter()
+                            .filter_map(|&j| reached[j][t - 1])
+                            .map(Lit::positive),
+                    );
+                    self.clauses.push(clause);
+                }
+
+                self.clauses
+                    .push(vec![!member, Lit::positive(reached[i][size - 1].unwrap())]);
+            }
+        }
+
+        let white_cells = self.islands.iter().map(|&(_, size)| size).sum::<usize>();
+        let black_cells = len.saturating_sub(white_cells);
+        if black_cells < 2 {
+            return;
+        }
+        let steps = black_cells - 1;
+
+        // Some cell before `i` is black.
+        let mut before = vec![None; len];
+        for i in 1..len {
+            let var = Lit::positive(self.new_var());
+            before[i] = Some(var);
+
+            self.clauses.push(vec![!self.black(i - 1), var]);
+            if let Some(previous) = before[i - 1] {
+                self.clauses.push(vec![!previous, var]);
+            }
+        }
+
+        // Cells reached from the first black cell in at most `t` steps.
+        let mut reached = vec![vec![]; len];
+        for cells in reached.iter_mut() {
+            for _ in 0..=steps {
+                cells.push(Lit::positive(self.new_var()));
+            }
+        }
+
+        for i in 0..len {
+            let black = self.black(i);
+
+            self.clauses.push(vec![!reached[i][0], black]);
+            if let Some(before) = before[i] {
+                self.clauses.push(vec![!reached[i][0], !before]);
+            }
+
+            for t in 1..=steps {
+                self.clauses.push(vec![!reached[i][t], black]);
+
+                let mut clause = vec![!reached[i][t], reached[i][t - 1]];
+                clause.extend(self.board.neighbours(i).iter().map(|&j| reached[j][t - 1]));
+                self.clauses.push(clause);
+            }
+
+            self.clauses.push(vec![!black, reached[i][steps]]);
+        }
+    }
+
+    /// Grid of the model in the format of `Nurikabe`, clues keep their size.
+    ///
+    pub fn to_data(&self, model: &[bool]) -> Vec<i32> {
+        let mut data = vec![WHITE; self.board.len()];
+
+        for (i, v) in data.iter_mut().enumerate() {
+            if model[i] {
+                *v = BLACK;
+            }
+        }
+        for &(i, size) in self.islands.iter() {
+            data[i] = size as i32;
+        }
+
+        data
+    }
+
+    /// Clauses that forbid the disconnected parts of the model. Empty when the
+    /// islands and the river are connected.
+    ///
+    pub fn cuts(&self, model: &[bool]) -> Vec<Vec<Lit>> {
+        let mut cuts = vec![];
+
+        // A part of an island away from its clue must grow through one of its
+        // neighbours to reach the clue.
+        for (k, &(clue, _)) in self.islands.iter().enumerate() {
+            let in_island = |i: usize| self.member(i, k).is_some_and(|var| model[var]);
+
+            let mut reached = self.board.flood(clue, in_island);
+            for start in 0..self.board.len() {
+                if reached.contains(start) || !in_island(start) {
+                    continue;
+                }
+
+                let part = self.board.flood(start, in_island);
+                let border = self.border(&part);
+
+                for i in part.iter() {
+                    let mut cut = vec![!Lit::positive(self.member(i, k).unwrap())];
+                    cut.extend(
+                        border
+                            .iter()
+                            .filter_map(|&j| self.member(j, k))
+                            .map(Lit::positive),
+                    );
+                    cuts.push(cut);
+                }
+
+                reached.union(&part);
+            }
+        }
+
+        // Same for parts of the river, which must reach the largest part.
+        let is_black = |i: usize| model[i];
+        let mut parts = vec![];
+        let mut reached = Cells::new(self.board.len());
+        for start in 0..self.board.len() {
+            if reached.contains(start) || !is_black(start) {
+                continue;
+            }
+
+            let part = self.board.flood(start, is_black);
+            reached.union(&part);
+            parts.push(part);
+        }
+
+        if let Some(largest) = parts.iter().map(|part| part.len()).max() {
+            let main = parts.iter().find(|part| part.len() == largest).unwrap();
+            let target = main.iter().next().unwrap();
+
+            for part in parts.iter().filter(|part| !part.contains(target)) {
+                let border = self.border(part);
+
+                for i in part.iter() {
+                    let mut cut = vec![!self.black(i), !self.black(target)];
+                    cut.extend(border.iter().map(|&j| self.black(j)));
+                    cuts.push(cut);
+                }
+            }
+        }
+
+        cuts
+    }
+
+    /// Cells next to the part, but not in it.
+    ///
+    fn border(&self, part: &Cells) -> Vec<usize> {
+        let mut border = Cells::new(self.board.len());
+        for i in part.iter() {
+            for &j in self.board.neighbours(i) {
+                if !part.contains(j) {
+                    border.insert(j);
+                }
+            }
+        }
+
+        border.iter().collect()
+    }
+
+    fn new_var(&mut self) -> Var {
+        self.num_vars += 1;
+        self.num_vars - 1
+    }
+
+    /// Island variables for the cells an island can reach. Cells next to
+    /// another clue can't be part of the island, they would join the two.
+    ///
+    fn add_members(&mut self) {
+        let mut claimed = vec![vec![]; self.board.len()];
+        for (k, &(clue, _)) in self.islands.iter().enumerate() {
+            claimed[clue].push(k);
+            for &j in self.board.neighbours(clue) {
+                claimed[j].push(k);
+            }
+        }
+
+        for k in 0..self.islands.len() {
+            let (clue, size) = self.islands[k];
+
+            let mut distance = vec![usize::MAX; self.board.len()];
+            let mut queue = VecDeque::from([clue]);
+            distance[clue] = 0;
+
+            while let Some(i) = queue.pop_front() {
+                let var = self.new_var();
+                self.members[i].push((k, var));
+
+                if distance[i] + 1 >= size {
+                    continue;
+                }
+
+                for &j in self.board.neighbours(i) {
+                    if distance[j] == usize::MAX && claimed[j].iter().all(|&other| other == k) {
+                        distance[j] = distance[i] + 1;
+                        queue.push_back(j);
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_cells(&mut self) {
+        let white_cells = self.islands.iter().map(|&(_, size)| size).sum::<usize>();
+        let river = self.board.len() > white_cells + 1;
+
+        for &(clue, _) in self.islands.iter() {
+            self.clauses.push(vec![!self.black(clue)]);
+        }
+        for (k, &(clue, _)) in self.islands.iter().enumerate() {
+            self.clauses
+                .push(vec![Lit::positive(self.member(clue, k).unwrap())]);
+        }
+
+        for i in 0..self.board.len() {
+            let black = self.black(i);
+            let members = self.members[i].clone();
+
+            // White cell is part of an island, but only of one.
+            let mut clause = vec![black];
+            clause.extend(members.iter().map(|&(_, var)| Lit::positive(var)));
+            self.clauses.push(clause);
+
+            for (a, &(_, var)) in members.iter().enumerate() {
+                self.clauses.push(vec![!Lit::positive(var), !black]);
+
+                for &(_, other) in members[a + 1..].iter() {
+                    self.clauses
+                        .push(vec![!Lit::positive(var), !Lit::positive(other)]);
+                }
+            }
+
+            // White neighbours of an island cell are in the same island.
+            for &j in self.board.neighbours(i) {
+                for &(k, var) in members.iter() {
+                    let mut clause = vec![!Lit::positive(var), self.black(j)];
+                    if let Some(other) = self.member(j, k) {
+                        clause.push(Lit::positive(other));
+                    }
+                    self.clauses.push(clause);
+                }
+            }
+
+            // River of more than one cell can't have lonely cells.
+            if river {
+                let mut clause = vec![!black];
+                clause.extend(self.board.neighbours(i).iter().map(|&j| self.black(j)));
+                self.clauses.push(clause);
+            }
+        }
+    }
+
+    fn add_pools(&mut self) {
+        let width = self.width;
+
+        for x in 0..self.height.saturating_sub(1) {
+            for y in 0..width.saturating_sub(1) {
+                let i = self.board.index(x, y);
+                self.clauses.push(vec![
+                    !self.black(i),
+                    !self.black(i + 1),
+                    !self.black(i + width),
+                    !self.black(i + width + 1),
+                ]);
+            }
+        }
+    }
+
+    fn add_sizes(&mut self) {
+        for k in 0..self.islands.len() {
+            let size = self.islands[k].1;
+            let lits = (0..self.board.len())
+                .filter_map(|i| self.member(i, k))
+                .map(Lit::positive)
+                .collect::<Vec<_>>();
+
+            self.exactly(&lits, size);
+        }
+    }
+
+    /// Exactly `num` of the literals are true, with a sequential counter.
+    /// Counter `j` of a literal is true when at least `j` of the literals up to
+    /// it are true.
+    ///
+    fn exactly(&mut self, lits: &[Lit], num: usize) {
+        // Counters of the previous literal, `None` is false. Index 0 is unused,
+        // "at least 0" is always true.
+        let mut previous: Vec<Option<Lit>> = vec![None; num + 2];
+
+        for (i, &lit) in lits.iter().enumerate() {
+            let mut current = vec![None; num + 2];
+
+            for j in 1..=(i + 1).min(num + 1) {
+                let counter = Lit::positive(self.new_var());
+                let before = previous[j];
+                let below = previous[j - 1];
+
+                // counter <-> before | (lit & below)
+                if let Some(before) = before {
+                    self.clauses.push(vec![!before, counter]);
+                }
+                match (j, below) {
+                    (1, _) => self.clauses.push(vec![!lit, counter]),
+                    (_, Some(below)) => self.clauses.push(vec![!lit, !below, counter]),
+                    _ => {}
+                }
+
+                let mut clause = vec![!counter, lit];
+                clause.extend(before);
+                self.clauses.push(clause);
+
+                if j > 1 {
+                    let mut clause = vec![!counter];
+                    clause.extend(before);
+                    clause.extend(below);
+                    self.clauses.push(clause);
+                }
+
+                current[j] = Some(counter);
+            }
+
+            previous = current;
+        }
+
+        if num > 0 {
+            self.clauses.push(previous[num].into_iter().collect());
+        }
+        if let Some(over) = previous[num + 1] {
+            self.clauses.push(vec![!over]);
+        }
+    }
+}
+
+/// Exact solver. The puzzle is encoded as clauses (see `Encoding`) and solved
+/// with the CDCL solver of `cdcl`. A model with disconnected islands or river
+/// is forbidden with more clauses and the search goes on, until a model is
+/// connected or there are no more models.
+///
+#[derive(Debug)]
+pub struct SatSolver {
+    path: String,
+    encoding: Encoding,
+    cdcl: Cdcl,
+    data: Vec<i32>,
+    solved: bool,
+    iteration: usize,
+    /// Clauses added for disconnected models.
+    cuts: usize,
+    explenation: String,
+    pub verbose: bool,
+}
+
+impl SatSolver {
+    pub fn new(nurikabe: Nurikabe) -> Self {
+        let encoding = Encoding::new(&nurikabe);
+
+        let mut cdcl = Cdcl::new();
+        for _ in 0..encoding.num_vars {
+            cdcl.new_var();
+        }
+        for clause in encoding.clauses.iter() {
+            cdcl.add_clause(clause);
+        }
+
+        let data = nurikabe
+            .data
+            .iter()
+            .map(|&v| if v > 0 { v } else { UNKNOWN })
+            .collect();
+
+        Self {
+            path: nurikabe.path,
+            encoding,
+            cdcl,
+            data,
+            solved: false,
+            iteration: 0,
+            cuts: 0,
+            explenation: String::new(),
+            verbose: false,
+        }
+    }
+}
+
+impl Solver for SatSolver {
+    fn solve(&mut self) -> Step {
+        if self.solved {
+            return Step::SolutionFound;
+        }
+
+        self.iteration += 1;
+
+        match self.cdcl.solve(CONFLICTS) {
+            Outcome::Unknown => {
+                self.explenation = format!("Searching, {} conflicts.", self.cdcl.conflicts);
+                Step::Proceed
+            }
+            Outcome::Unsat => {
+                self.explenation = format!(
+                    "Puzzle has no solution ({} conflicts, {} cuts).",
+                    self.cdcl.conflicts, self.cuts
+                );
+                Step::Contradiction
+            }
+            Outcome::Sat => {
+                let model = self.cdcl.model();
+                self.data = self.encoding.to_data(model);
+
+                let cuts = self.encoding.cuts(model);
+                if cuts.is_empty() {
+                    self.solved = true;
+                    self.explenation = format!(
+                        "Puzzle solved! ({} conflicts, {} cuts)",
+                        self.cdcl.conflicts, self.cuts
+                    );
+                    return Step::SolutionFound;
+                }
+
+                self.explenation = format!("Model isn't connected, adding {} cuts.", cuts.len());
+                self.cuts += cuts.len();
+                for cut in cuts.iter() {
+                    self.cdcl.add_clause(cut);
+                }
+
+                Step::Proceed
+            }
+        }
+    }
+
+    fn get_state(&self) -> Nurikabe {
+        let verbose = if self.verbose {
+            self.explenation.clone()
+        } else {
+            String::from("")
+        };
+
+        Nurikabe {
+            path: self.path.clone(),
+            width: self.encoding.width,
+            height: self.encoding.height,
+            solved: self.solved,
+            iteration: self.iteration,
+            data: self.data.clone(),
+            duration: 0,
+            seed: None,
+            verbose,
+        }
+    }
+
+    fn get_iteration(&self) -> usize {
+        self.iteration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::tests::{data_puzzles, unsolvable_puzzle};
+    use crate::solvers::backtrack::BacktrackSolver;
+    use crate::verify::verify;
+
+    fn run(mut solver: impl Solver) -> (Step, Nurikabe) {
+        loop {
+            match solver.solve() {
+                Step::Proceed => (),
+                step => return (step, solver.get_state()),
+            }
+        }
+    }
+
+    fn black(nurikabe: &Nurikabe) -> Vec<bool> {
+        nurikabe.data.iter().map(|&v| v == BLACK).collect()
+    }
+
+    #[test]
+    fn matches_backtrack() {
+        for (name, puzzle) in data_puzzles() {
+            let (step, solution) = run(SatSolver::new(puzzle.clone()));
+
+            assert_eq!(step, Step::SolutionFound, "{}", name);
+            assert_eq!(verify(&puzzle, &solution), vec![], "{}", name);
+
+            // Has many solutions and takes the backtracking search seconds.
+            if name.ends_with("nurikabe3.csv") {
+                continue;
+            }
+
+            let (step, expected) = run(BacktrackSolver::new(puzzle));
+            assert_eq!(step, Step::SolutionFound, "{}", name);
+            assert_eq!(black(&solution), black(&expected), "{}", name);
+        }
+    }
+
+    #[test]
+    fn unsolvable() {
+        let (step, _) = run(SatSolver::new(unsolvable_puzzle()));
+        assert_eq!(step, Step::Contradiction);
+    }
+}
@@ -7,6 +7,12 @@ pub enum State {
     Island(i32),
 }
 
+/// Values of the states in the data of a `Nurikabe`.
+///
+pub const UNKNOWN: i32 = State::Unknown.value();
+pub const WHITE: i32 = State::White.value();
+pub const BLACK: i32 = State::Black.value();
+
 impl State {
     pub fn new(val: i32) -> Self {
         match val {
@@ -14,11 +20,12 @@ impl State {
             _ => State::Island(val),
         }
     }
-}
 
-impl From<State> for i32 {
-   	fn from(val: State) -> Self {
-        match val {
+    /// Value of the state in the data of a `Nurikabe`, islands are their
+    /// clue.
+    ///
+    pub const fn value(self) -> i32 {
+        match self {
             State::Unknown => -3,
             State::White => -2,
             State::Black => -1,
@@ -26,3 +33,9 @@ impl From<State> for i32 {
         }
     }
 }
+
+impl From<State> for i32 {
+   	fn from(val: State) -> Self {
+        val.value()
+    }
+}
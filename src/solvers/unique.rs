@@ -0,0 +1,114 @@
+use super::backtrack::BacktrackSolver;
+use super::*;
+
+/// Number of solutions of a puzzle, as reported by `uniqueness`.
+///
+#[derive(Debug, Clone)]
+pub enum Uniqueness {
+    /// Puzzle has no solution.
+    Unsolvable,
+    Unique(Nurikabe),
+    /// Puzzle has more than one solution, two of them are given as witnesses.
+    Many(Nurikabe, Nurikabe),
+}
+
+/// Enumerates solutions of the puzzle with the complete backtracking search.
+/// Search stops after `limit` solutions are found.
+///
+pub fn solutions(nurikabe: Nurikabe, limit: usize) -> Vec<Nurikabe> {
+    search(nurikabe, limit, usize::MAX).unwrap_or_default()
+}
+
+/// Checks if the puzzle has exactly one solution.
+///
+pub fn uniqueness(nurikabe: Nurikabe) -> Uniqueness {
+    into_uniqueness(solutions(nurikabe, 2))
+}
+
+/// Same as `uniqueness`, but gives up after `max_iter` backtracking steps.
+/// Returns `None` when the search didn't finish.
+///
+pub fn uniqueness_within(nurikabe: Nurikabe, max_iter: usize) -> Option<Uniqueness> {
+    search(nurikabe, 2, max_iter).map(into_uniqueness)
+}
+
+/// Collects up to `limit` solutions. Returns `None` when the search runs out
+/// of iterations first.
+///
+fn search(nurikabe: Nurikabe, limit: usize, max_iter: usize) -> Option<Vec<Nurikabe>> {
+    let mut solver = BacktrackSolver::new(nurikabe);
+    let mut solutions = vec![];
+
+    while solutions.len() < limit {
+        if solver.get_iteration() >= max_iter {
+            return None;
+        }
+
+        match solver.solve() {
+            Step::Proceed => (),
+            Step::SolutionFound => {
+                solutions.push(solver.get_state());
+                solver.resume();
+            }
+            _ => break,
+        }
+    }
+
+    Some(solutions)
+}
+
+fn into_uniqueness(solutions: Vec<Nurikabe>) -> Uniqueness {
+    let mut solutions = solutions.into_iter();
+
+    match (solutions.next(), solutions.next()) {
+        (Some(first), Some(second)) => Uniqueness::Many(first, second),
+        (Some(solution), None) => Uniqueness::Unique(solution),
+        _ => Uniqueness::Unsolvable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::load_nurikabe;
+    use crate::nurikabe::tests::unsolvable_puzzle;
+    use crate::verify::verify;
+
+    #[test]
+    fn unique() {
+        for input in [
+            include_str!("../../data/nurikabe5x5.csv"),
+            include_str!("../../data/nurikabe10x10v2.csv"),
+        ] {
+            let puzzle = load_nurikabe(input).unwrap();
+
+            match uniqueness(puzzle.clone()) {
+                Uniqueness::Unique(solution) => assert_eq!(verify(&puzzle, &solution), vec![]),
+                result => panic!("Expected a unique solution, got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn many() {
+        // Island of the 2 can take either neighbour.
+        let puzzle = load_nurikabe("0,2,0").unwrap();
+
+        match uniqueness(puzzle.clone()) {
+            Uniqueness::Many(first, second) => {
+                assert_ne!(first.data, second.data);
+                assert_eq!(verify(&puzzle, &first), vec![]);
+                assert_eq!(verify(&puzzle, &second), vec![]);
+            }
+            result => panic!("Expected many solutions, got {:?}", result),
+        }
+
+        assert_eq!(solutions(puzzle, 10).len(), 2);
+    }
+
+    #[test]
+    fn unsolvable() {
+        let result = uniqueness(unsolvable_puzzle());
+        assert!(matches!(result, Uniqueness::Unsolvable));
+    }
+}
@@ -0,0 +1,310 @@
+use std::{collections::VecDeque, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::nurikabe::Nurikabe;
+use crate::solvers::for_valid_neighbours;
+use crate::solvers::state::{BLACK, UNKNOWN, WHITE};
+
+/// Rule of nurikabe that a solution can break.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rule {
+    /// Solution has different dimensions than the puzzle.
+    Dimensions,
+    /// Clue of the puzzle is marked black in the solution.
+    Clue,
+    /// Cell is neither black nor white.
+    Unknown,
+    /// 2x2 block of black cells.
+    Pool,
+    /// Black cells aren't connected in a single river.
+    River,
+    /// White cells that aren't part of any numbered island.
+    Orphan,
+    /// Island with more than one clue.
+    Clues,
+    /// Island with a different number of cells than its clue.
+    Size,
+}
+
+/// Broken rule, with the cells that break it as (row, column).
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation {
+    pub rule: Rule,
+    pub cells: Vec<(usize, usize)>,
+    pub message: String,
+}
+
+impl Violation {
+    fn new(rule: Rule, cells: Vec<(usize, usize)>, message: String) -> Self {
+        Self {
+            rule,
+            cells,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        let cells = self
+            .cells
+            .iter()
+            .map(|(x, y)| format!("({}, {})", x, y))
+            .collect::<Vec<_>>();
+
+        write!(f, ": {}", cells.join(" "))
+    }
+}
+
+/// Checks if the grid is a valid solution of the puzzle and returns every
+/// broken rule. Empty list means the solution is valid.
+///
+/// Clues are taken from the puzzle. In the solution black cells are `-1`, any
+/// other known value is white (clue cells, `-2` and island sizes as the rule
+/// solver reports them).
+///
+pub fn verify(puzzle: &Nurikabe, solution: &Nurikabe) -> Vec<Violation> {
+    let (width, height) = (puzzle.width, puzzle.height);
+
+    if solution.width != width
+        || solution.height != height
+        || solution.data.len() != width * height
+        || puzzle.data.len() != width * height
+    {
+        return vec![Violation::new(
+            Rule::Dimensions,
+            vec![],
+            format!(
+                "Solution is {} x {}, puzzle is {} x {}",
+                solution.width, solution.height, width, height
+            ),
+        )];
+    }
+
+    let cell = |i: usize| (i / width, i % width);
+
+    // Clues and colours, as the rules see them.
+    let data = solution
+        .data
+        .iter()
+        .zip(puzzle.data.iter())
+        .map(|(&v, &clue)| match v {
+            BLACK => BLACK,
+            _ if clue > 0 => clue,
+            WHITE | 1.. => WHITE,
+            _ => UNKNOWN,
+        })
+        .collect::<Vec<_>>();
+
+    let mut violations = vec![];
+
+    let clues = (0..data.len())
+        .filter(|&i| puzzle.data[i] > 0 && solution.data[i] == BLACK)
+        .map(cell)
+        .collect::<Vec<_>>();
+    if !clues.is_empty() {
+        violations.push(Violation::new(
+            Rule::Clue,
+            clues,
+            String::from("Clues are marked black"),
+        ));
+    }
+
+    let unknown = (0..data.len())
+        .filter(|&i| data[i] == UNKNOWN)
+        .map(cell)
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        violations.push(Violation::new(
+            Rule::Unknown,
+            unknown,
+            String::from("Cells are not decided"),
+        ));
+    }
+
+    for x in 0..height.saturating_sub(1) {
+        for y in 0..width.saturating_sub(1) {
+            let block = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)];
+            if block.iter().all(|&(a, b)| data[a * width + b] == BLACK) {
+                violations.push(Violation::new(
+                    Rule::Pool,
+                    block.to_vec(),
+                    String::from("Pool of black cells"),
+                ));
+            }
+        }
+    }
+
+    // Connected regions of black and of white cells.
+    let mut reached = vec![false; data.len()];
+    let mut rivers = vec![];
+
+    for start in 0..data.len() {
+        if reached[start] || data[start] == UNKNOWN {
+            continue;
+        }
+
+        let black = data[start] == BLACK;
+        let mut region = vec![];
+        let mut queue = VecDeque::from([start]);
+        reached[start] = true;
+
+        while let Some(i) = queue.pop_front() {
+            region.push(i);
+
+            for_valid_neighbours(width, height, i / width, i % width, |a, b| {
+                let j = a * width + b;
+                if !reached[j] && data[j] != UNKNOWN && (data[j] == BLACK) == black {
+                    reached[j] = true;
+                    queue.push_back(j);
+                }
+            });
+        }
+
+        if black {
+            rivers.push(region);
+            continue;
+        }
+
+        let clues = region.iter().filter(|&&i| data[i] > 0).collect::<Vec<_>>();
+        let cells = region.iter().map(|&i| cell(i)).collect::<Vec<_>>();
+
+        match clues[..] {
+            [] => violations.push(Violation::new(
+                Rule::Orphan,
+                cells,
+                String::from("White cells are not connected to any clue"),
+            )),
+            [&clue] if data[clue] as usize != region.len() => {
+                violations.push(Violation::new(
+                    Rule::Size,
+                    cells,
+                    format!(
+                        "Island of clue {} has {} cells",
+                        data[clue],
+                        region.len()
+                    ),
+                ))
+            }
+            [_] => (),
+            _ => violations.push(Violation::new(
+                Rule::Clues,
+                cells,
+                format!("Island has {} clues", clues.len()),
+            )),
+        }
+    }
+
+    // Largest part is taken as the river, other parts are reported.
+    if rivers.len() > 1 {
+        rivers.sort_by_key(|river| std::cmp::Reverse(river.len()));
+        let parts = rivers.len();
+
+        let cells = rivers[1..].iter().flatten().map(|&i| cell(i)).collect();
+        violations.push(Violation::new(
+            Rule::River,
+            cells,
+            format!("River is split in {} parts", parts),
+        ));
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nurikabe::tests::grid;
+
+    fn puzzle() -> Nurikabe {
+        grid(&["2??", "???", "1?1"])
+    }
+
+    /// Violation of the rule, the test fails when there is none.
+    ///
+    fn violation(solution: &[&str], rule: Rule) -> Violation {
+        verify(&puzzle(), &grid(solution))
+            .into_iter()
+            .find(|violation| violation.rule == rule)
+            .unwrap_or_else(|| panic!("{:?} is not broken by {:?}", rule, solution))
+    }
+
+    #[test]
+    fn valid_solution() {
+        assert_eq!(verify(&puzzle(), &grid(&["2.#", "###", "1#1"])), vec![]);
+    }
+
+    #[test]
+    fn dimensions() {
+        let violations = verify(&puzzle(), &grid(&["2.", "##"]));
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, Rule::Dimensions);
+    }
+
+    #[test]
+    fn clue() {
+        assert_eq!(
+            violation(&["#.#", "###", "1#1"], Rule::Clue).cells,
+            vec![(0, 0)]
+        );
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(
+            violation(&["2?#", "###", "1#1"], Rule::Unknown).cells,
+            vec![(0, 1)]
+        );
+    }
+
+    #[test]
+    fn pool() {
+        assert_eq!(
+            violation(&["2##", "###", "1#1"], Rule::Pool).cells,
+            vec![(0, 1), (1, 1), (0, 2), (1, 2)]
+        );
+    }
+
+    #[test]
+    fn river() {
+        let violation = violation(&["2.#", "#.#", "1#1"], Rule::River);
+
+        assert_eq!(violation.message, "River is split in 3 parts");
+        assert_eq!(violation.cells.len(), 2);
+    }
+
+    #[test]
+    fn orphan() {
+        let puzzle = grid(&["2???", "????", "1?1?"]);
+        let solution = grid(&["2.##", "###.", "1#1#"]);
+
+        let violations = verify(&puzzle, &solution);
+        let orphan = violations
+            .iter()
+            .find(|violation| violation.rule == Rule::Orphan)
+            .unwrap();
+        assert_eq!(orphan.cells, vec![(1, 3)]);
+    }
+
+    #[test]
+    fn clues() {
+        let violation = violation(&["2.#", ".##", "1#1"], Rule::Clues);
+
+        assert_eq!(violation.cells, vec![(0, 0), (1, 0), (0, 1), (2, 0)]);
+        assert_eq!(violation.message, "Island has 2 clues");
+    }
+
+    #[test]
+    fn size() {
+        let violation = violation(&["2##", "###", "1#1"], Rule::Size);
+
+        assert_eq!(violation.cells, vec![(0, 0)]);
+        assert_eq!(violation.message, "Island of clue 2 has 1 cells");
+    }
+}
@@ -0,0 +1,596 @@
+use std::{cell::RefCell, rc::Rc, sync::atomic::Ordering};
+
+use gloo_utils::format::JsValueSerdeExt;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use web_sys::{HtmlElement, HtmlInputElement, MessageEvent};
+
+use crate::console_log;
+use crate::nurikabe::{parse_nurikabe, to_url, Nurikabe};
+use crate::solvers::hint::Hints;
+use crate::solvers::Step;
+use crate::verify::verify;
+use crate::{is_better, rule_trace, run_solver, split_restarts, Progress, Properties, Session};
+
+/// Reads a puzzle file or a puzz.link URL, the format is picked from the
+/// content.
+///
+#[wasm_bindgen]
+pub fn load(input: &str) -> Result<JsValue, String> {
+    let nurikabe: Nurikabe = parse_nurikabe(input)?;
+
+    let result = serde_wasm_bindgen::to_value(&nurikabe).map_err(|error| format!("{}", error))?;
+    Ok(result)
+}
+
+/// Link to the puzzle on puzz.link.
+///
+#[wasm_bindgen]
+pub fn puzzle_url(nurikabe: JsValue) -> Result<String, String> {
+    let nurikabe =
+        JsValue::into_serde::<Nurikabe>(&nurikabe).map_err(|_| "Expects nurikabe puzzle")?;
+
+    Ok(to_url(&nurikabe))
+}
+
+/// Checks a filled grid against the puzzle. Returns the list of broken rules,
+/// which is empty for a valid solution.
+///
+#[wasm_bindgen]
+pub fn verify_solution(puzzle: JsValue, solution: JsValue) -> Result<JsValue, String> {
+    let puzzle = JsValue::into_serde::<Nurikabe>(&puzzle).map_err(|_| "Expects nurikabe puzzle")?;
+    let solution =
+        JsValue::into_serde::<Nurikabe>(&solution).map_err(|_| "Expects nurikabe solution")?;
+
+    let violations = verify(&puzzle, &solution);
+
+    serde_wasm_bindgen::to_value(&violations).map_err(|error| format!("{}", error))
+}
+
+thread_local! {
+    /// Solution of the last puzzle `get_hint` was asked about.
+    static HINTS: RefCell<Option<Hints>> = const { RefCell::new(None) };
+}
+
+/// Next deduction for the player grid, with the cells the player marked
+/// wrongly. The puzzle is solved once and kept for the following hints.
+///
+#[wasm_bindgen]
+pub fn get_hint(puzzle: JsValue, player: JsValue) -> Result<JsValue, String> {
+    let puzzle = JsValue::into_serde::<Nurikabe>(&puzzle).map_err(|_| "Expects nurikabe puzzle")?;
+    let player = JsValue::into_serde::<Nurikabe>(&player).map_err(|_| "Expects player grid")?;
+
+    let hint = HINTS.with_borrow_mut(|hints| {
+        let hints = match hints {
+            Some(hints) if hints.is_for(&puzzle) => hints,
+            _ => hints.insert(Hints::new(puzzle)?),
+        };
+        hints.hint(&player)
+    })?;
+
+    serde_wasm_bindgen::to_value(&hint).map_err(|error| format!("{}", error))
+}
+
+/// Solves the puzzle with the rules and returns every iteration, so the
+/// view can replay the solve.
+///
+#[wasm_bindgen]
+pub fn trace_rule_solver(nurikabe: JsValue) -> Result<JsValue, String> {
+    let nurikabe =
+        JsValue::into_serde::<Nurikabe>(&nurikabe).map_err(|_| "Expects nurikabe puzzle")?;
+
+    let trace = rule_trace(nurikabe);
+
+    serde_wasm_bindgen::to_value(&trace).map_err(|error| format!("{}", error))
+}
+
+#[wasm_bindgen]
+pub struct NurikabeApp {
+    session: Option<Session>,
+}
+
+#[wasm_bindgen]
+impl NurikabeApp {
+    pub fn new() -> Self {
+        Self { session: None }
+    }
+
+    /// Do work in separate thread.
+    ///
+    pub fn start_solver(&mut self, properties: JsValue) -> Result<JsValue, String> {
+        let properties = JsValue::into_serde::<Properties>(&properties)
+            .map_err(|_| "Expects properties objects")?;
+
+        let nurikabe = run_solver(properties)?;
+
+        serde_wasm_bindgen::to_value(&nurikabe).map_err(|error| format!("{}", error))
+    }
+
+    /// Starts a solve that is run with `advance`, replacing the previous one.
+    ///
+    pub fn start_session(&mut self, properties: JsValue) -> Result<(), String> {
+        let properties = JsValue::into_serde::<Properties>(&properties)
+            .map_err(|_| "Expects properties objects")?;
+
+        self.session = Some(Session::new(properties)?);
+        Ok(())
+    }
+
+    /// Runs at most `iterations` more iterations. Returns `true` when the
+    /// solve is finished.
+    ///
+    pub fn advance(&mut self, iterations: usize) -> Result<bool, String> {
+        let session = self.session.as_mut().ok_or("No solve started")?;
+
+        session.advance(iterations);
+        Ok(session.is_finished())
+    }
+
+    /// Stops the solve before its next iteration, `advance` then reports it
+    /// as finished.
+    ///
+    pub fn cancel(&mut self) {
+        if let Some(session) = &self.session {
+            session.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Current `Progress` of the solve.
+    ///
+    pub fn get_progress(&self) -> Result<JsValue, String> {
+        let session = self.session.as_ref().ok_or("No solve started")?;
+
+        serde_wasm_bindgen::to_value(&session.get_progress()).map_err(|error| format!("{}", error))
+    }
+}
+
+impl Default for NurikabeApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+pub fn startup() {
+    set_panic_hook();
+
+	console_log!("WASM Startup");
+
+    let window = web_sys::window().unwrap();
+    let size = (window.navigator().hardware_concurrency() as usize).max(1);
+
+	console_log!("Worker pool of {}", size);
+
+    let pool = Rc::new(RefCell::new(Pool::new(size)));
+
+    let on_message = get_on_msg_callback(pool.clone());
+    pool.borrow_mut().on_message = on_message.as_ref().clone();
+    on_message.forget();
+
+    setup_callbacks(pool);
+}
+
+/// How often a worker posts its progress, in ms.
+///
+const PROGRESS_INTERVAL: f64 = 100.0;
+
+/// Solve posted to a worker of the pool.
+///
+#[derive(Serialize)]
+struct Job {
+    worker: usize,
+    interval: f64,
+    properties: Properties,
+}
+
+/// Stops the solve of a worker.
+///
+#[derive(Serialize)]
+struct Cancel {
+    cancel: bool,
+}
+
+/// Progress posted back by a worker.
+///
+#[derive(Deserialize)]
+struct Report {
+    worker: usize,
+    progress: Progress,
+}
+
+/// Module workers solving the same puzzle with different seeds. The best
+/// state of all workers is shown while they run, and the run is over once
+/// every worker finished, or as soon as one solves it.
+///
+struct Pool {
+    size: usize,
+    workers: Vec<web_sys::Worker>,
+    /// Message callback shared by every worker.
+    on_message: JsValue,
+    puzzle: Nurikabe,
+    /// Latest progress of each worker of the run.
+    latest: Vec<Option<Progress>>,
+    running: bool,
+}
+
+impl Pool {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            workers: vec![],
+            on_message: JsValue::NULL,
+            puzzle: Nurikabe::default(),
+            latest: vec![],
+            running: false,
+        }
+    }
+
+    /// Starts a run, cancelling the previous one if it's still going.
+    ///
+    fn start(&mut self, properties: Properties) {
+        if self.running || self.workers.is_empty() {
+            self.spawn();
+        }
+
+        let restarts = split_restarts(&properties, self.size);
+
+        self.puzzle = properties.nurikabe;
+        self.latest = vec![None; restarts.len()];
+        self.running = true;
+
+        for (worker, (k, properties)) in self.workers.iter().zip(restarts.into_iter().enumerate()) {
+            let job = Job {
+                worker: k,
+                interval: PROGRESS_INTERVAL,
+                properties,
+            };
+            match serde_wasm_bindgen::to_value(&job) {
+                Ok(message) => {
+                    let _ = worker.post_message(&message);
+                }
+                Err(error) => {
+                    // Counts as finished, so the run doesn't wait on it.
+                    console_log!("Job {} not sent: {}", k, error);
+                    self.latest[k] = Some(Progress {
+                        state: self.puzzle.clone(),
+                        score: None,
+                        step: Step::CannotProceed,
+                        finished: true,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Asks the workers to stop, they post their best state as finished.
+    ///
+    fn cancel(&self) {
+        if !self.running {
+            return;
+        }
+
+        let message = serde_wasm_bindgen::to_value(&Cancel { cancel: true }).unwrap();
+        for worker in self.workers.iter() {
+            let _ = worker.post_message(&message);
+        }
+    }
+
+    /// Records the progress of one worker and returns the best progress of the
+    /// run, which is `finished` when the run is over.
+    ///
+    fn receive(&mut self, report: Report) -> Option<Progress> {
+        if !self.running {
+            return None;
+        }
+        self.latest[report.worker] = Some(report.progress);
+
+        let mut best: Option<&Progress> = None;
+        for progress in self.latest.iter().flatten() {
+            if best.is_none_or(|best| is_better(&self.puzzle, &progress.state, &best.state)) {
+                best = Some(progress);
+            }
+        }
+        let mut best = best?.clone();
+
+        let done = self
+            .latest
+            .iter()
+            .all(|progress| progress.as_ref().is_some_and(|progress| progress.finished));
+
+        if best.state.solved && !done {
+            // The others are still busy, start fresh ones on the next run.
+            self.terminate();
+        }
+
+        self.running = !(done || best.state.solved);
+        best.finished = !self.running;
+
+        Some(best)
+    }
+
+    fn spawn(&mut self) {
+        self.terminate();
+
+        let worker_options = web_sys::WorkerOptions::new();
+        worker_options.set_type(web_sys::WorkerType::Module);
+
+        for _ in 0..self.size {
+            let worker = web_sys::Worker::new_with_options("./worker.js", &worker_options).unwrap();
+            worker.set_onmessage(Some(self.on_message.unchecked_ref()));
+            self.workers.push(worker);
+        }
+    }
+
+    fn terminate(&mut self) {
+        for worker in self.workers.drain(..) {
+            worker.terminate();
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub fn sum_of_squares(input: &[i32]) -> i32 {
+    input.iter() 
+         .map(|&i| i * i)
+         .sum()
+}
+
+#[wasm_bindgen]
+pub fn sum_of_squares_simple(input: &[i32]) -> i32 {
+    input.iter() 
+         .map(|&i| i * i)
+         .sum()
+}
+
+fn setup_callbacks(pool: Rc<RefCell<Pool>>) {
+    let window = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let stop_pool = pool.clone();
+    let on_stop_callback = Closure::wrap(Box::new(move || {
+        stop_pool.borrow().cancel();
+    }) as Box<dyn FnMut()>);
+
+    document
+        .get_element_by_id("stop")
+        .expect("#stop should exist")
+        .dyn_ref::<HtmlElement>()
+        .expect("#stop should be a HtmlElement")
+        .set_onclick(Some(on_stop_callback.as_ref().unchecked_ref()));
+
+    on_stop_callback.forget();
+
+    let on_click_callback = Closure::wrap(Box::new(move || {
+        let document = window.document().unwrap();
+
+		document
+            .get_element_by_id("progress")
+            .expect("#progress should exist")
+            .dyn_ref::<HtmlElement>()
+            .expect("#progress should be a HtmlInputElement")
+            .set_inner_text("In progress...");
+
+        let mut properties = Properties::default();
+
+        let get_element_by_id = document.get_element_by_id("ants");
+        properties.ants = get_element_by_id
+            .unwrap()
+            .dyn_ref::<HtmlInputElement>()
+            .unwrap()
+            .value()
+            .parse::<usize>()
+            .unwrap();
+
+        properties.max_iter = document
+            .get_element_by_id("max_iter")
+            .unwrap()
+            .dyn_ref::<HtmlInputElement>()
+            .unwrap()
+            .value()
+            .parse::<usize>()
+            .unwrap();
+
+        properties.l_evap = document
+            .get_element_by_id("local")
+            .unwrap()
+            .dyn_ref::<HtmlInputElement>()
+            .unwrap()
+            .value()
+            .parse::<f64>()
+            .unwrap();
+
+        properties.g_evap = document
+            .get_element_by_id("global")
+            .unwrap()
+            .dyn_ref::<HtmlInputElement>()
+            .unwrap()
+            .value()
+            .parse::<f64>()
+            .unwrap();
+
+        properties.greedines = document
+            .get_element_by_id("greedy")
+            .unwrap()
+            .dyn_ref::<HtmlInputElement>()
+            .unwrap()
+            .value()
+            .parse::<f64>()
+            .unwrap();
+
+        properties.bve = document
+            .get_element_by_id("bve")
+            .unwrap()
+            .dyn_ref::<HtmlInputElement>()
+            .unwrap()
+            .value()
+            .parse::<f64>()
+            .unwrap();
+
+        // Empty seed means a random one. Seeds are 32 bits, so they fit in a JS
+        // number.
+        properties.seed = document
+            .get_element_by_id("seed")
+            .unwrap()
+            .dyn_ref::<HtmlInputElement>()
+            .unwrap()
+            .value()
+            .parse::<u32>()
+            .ok()
+            .map(u64::from);
+
+        // Empty time limit means no limit.
+        properties.time_limit = document
+            .get_element_by_id("time_limit")
+            .unwrap()
+            .dyn_ref::<HtmlInputElement>()
+            .unwrap()
+            .value()
+            .parse::<u64>()
+            .ok();
+
+        let nurikabe = window.get("nurikabe").unwrap();
+        let method = window.get("method").unwrap();
+        properties.nurikabe = JsValue::into_serde::<Nurikabe>(&nurikabe).expect("Nurikabe!");
+        properties.method = JsValue::into_serde::<String>(&method).expect("Method!");
+
+        // Workers return their results asynchronously, to the callback of the pool.
+        pool.borrow_mut().start(properties);
+    }) as Box<dyn FnMut()>);
+
+    document
+        .get_element_by_id("solve")
+        .expect("#solve should exist")
+        .dyn_ref::<HtmlElement>()
+        .expect("#solve should be a HtmlElement")
+        .set_onclick(Some(on_click_callback.as_ref().unchecked_ref()));
+
+    on_click_callback.forget();
+}
+
+/// Create a closure to act on the message returned by a worker of the pool
+///
+fn get_on_msg_callback(pool: Rc<RefCell<Pool>>) -> Closure<dyn FnMut(MessageEvent)> {
+    Closure::wrap(Box::new(move |event: MessageEvent| {
+        let report: Report = event.data().into_serde().expect("Worker report.");
+
+        let Some(progress) = pool.borrow_mut().receive(report) else {
+            return;
+        };
+
+        let text = if progress.finished {
+            match progress.step {
+                Step::Timeout => String::from("Out of time."),
+                Step::Cancelled => String::from("Stopped."),
+                _ => String::from(""),
+            }
+        } else {
+            format!(
+                "In progress... iteration {}, score {}",
+                progress.state.iteration,
+                progress.score.map_or(String::from("-"), |score| score.to_string())
+            )
+        };
+
+		let window = web_sys::window().unwrap();
+		let document = window.document().unwrap();
+		document
+			.get_element_by_id("progress")
+			.expect("#progress should exist")
+			.dyn_ref::<HtmlElement>()
+			.expect("#progress should be a HtmlInputElement")
+			.set_inner_text(&text);
+
+        view_nurikabe(progress.state);
+    }) as Box<dyn FnMut(_)>)
+}
+
+/// Update nurikabe grid.
+fn view_nurikabe(nurikabe: Nurikabe) {
+    // let previous = window
+    //     .get("previous")
+    //     .map(|p| JsValue::into_serde::<Nurikabe>(&p).unwrap());
+
+    let window = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+	let parent = document.get_element_by_id("nurikabe").unwrap();
+	parent.set_inner_html("");
+
+    let width = nurikabe.width;
+    let height = nurikabe.height;
+
+    let grid_parent = document.create_element("div").unwrap();
+
+    if nurikabe.iteration > 0 {
+        let step_text = document.create_element("p").unwrap();
+        step_text.set_inner_html(&format!("{}. {}", nurikabe.iteration, nurikabe.verbose));
+        let _ = grid_parent.append_child(&step_text);
+    }
+
+    let grid = document.create_element("div").unwrap();
+    grid.set_class_name("grid");
+    grid.set_id("grid");
+
+    for i in 0..height {
+        let row = document.create_element("div").unwrap();
+        row.set_class_name("row");
+
+        for j in 0..width {
+            let value = nurikabe.data[i * width + j];
+            // let previous_value = match &previous {
+            //     Some(p) => p.data[i * width + j],
+            //     None => value,
+            // };
+
+            let cell = document.create_element("div").unwrap();
+
+            if value > 0 {
+                cell.set_inner_html(&format!("{}", value));
+            } else {
+                cell.set_inner_html(" ");
+            }
+
+            if false {
+                // window.get("previous_coloring").unwrap().as_bool().unwrap()
+                // && previous_value != value
+                if value == -1 {
+                    cell.set_class_name("new_black");
+                } else {
+                    cell.set_class_name("new");
+                }
+            } else {
+                match value {
+                    -3 => cell.set_class_name("unknown"),
+                    -2 => cell.set_class_name("white"),
+                    -1 => cell.set_class_name("black"),
+                    _ => cell.set_class_name("white"),
+                }
+            }
+            let _ = row.append_child(&cell);
+        }
+
+        let _ = grid.append_child(&row);
+    }
+
+    let _ = grid_parent.append_child(&grid);
+    let _ = parent.append_child(&grid_parent);
+
+    // Update properties
+    let properties = document.get_element_by_id("properties").unwrap();
+    properties.set_inner_html(&format!(
+        "<i>File:</i> {} <br/>
+		<i>Dims:</i> {} x {} <br/>
+		<i>Solved:</i> <b>{}</b> <br/>
+		<i>Iteration:</i> <b>{}</b> <br/>
+		<i>Seed:</i> {} <br/>
+		<i>Time:</i> {} ms<br/>",
+        nurikabe.path, nurikabe.width, nurikabe.height, nurikabe.solved, nurikabe.iteration,
+        nurikabe.seed.map_or(String::from("-"), |seed| seed.to_string()), nurikabe.duration
+    ));
+    // todo, add to window.
+}
+
+pub fn set_panic_hook() {
+    #[cfg(feature = "console_error_panic_hook")]
+    console_error_panic_hook::set_once();
+}
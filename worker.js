@@ -1,27 +1,62 @@
 // Great source: https://github.com/sgasse/wasm_worker_interaction/tree/main
 
-// This worker.js is called from WASM.
+// This worker.js is called from WASM, one per worker of the pool.
 
 import init, { NurikabeApp } from "./pkg/nurikabe.js";
 
-async function init_wasm_in_worker() {
-  await init();
-
+// Workers are spawned right before a run, so messages can arrive before the
+// module is loaded. They wait for it here.
+const ready = init().then(() => {
   console.log("Hello from worker!");
 
-  var app = NurikabeApp.new();
-
-  // Do heavy work in separate thread.
-  self.onmessage = async (event) => {
-    let start_time = performance.now();
-    let properties = event.data;
-    let nurikabe = app.start_solver(properties);
-    nurikabe.duration = parseInt(performance.now() - start_time);
-    
-    console.log(`Completed in: ${nurikabe.duration}`);
-
-    self.postMessage(nurikabe);
-  };
-}
-
-init_wasm_in_worker();
+  return NurikabeApp.new();
+});
+
+// Do heavy work in separate thread. The best state so far is posted every
+// `interval` ms and once more when the solve is finished. The worker yields
+// after each post, so a cancel message can stop the solve.
+self.onmessage = async (event) => {
+  let app = await ready;
+
+  if (event.data.cancel) {
+    app.cancel();
+    return;
+  }
+
+  let { worker, interval, properties } = event.data;
+  let start_time = performance.now();
+  let last_post = start_time;
+
+  try {
+    app.start_session(properties);
+
+    let finished = false;
+    while (!finished) {
+      finished = app.advance(1);
+
+      let now = performance.now();
+      if (finished || now - last_post >= interval) {
+        let progress = app.get_progress();
+        progress.state.duration = parseInt(now - start_time);
+        self.postMessage({ worker, progress });
+        last_post = now;
+
+        await new Promise((resolve) => setTimeout(resolve, 0));
+      }
+    }
+  } catch (error) {
+    // The pool waits for every worker, so a failed solve still reports the
+    // puzzle as finished.
+    console.error(error);
+    let progress = {
+      state: properties.nurikabe,
+      score: null,
+      step: "CannotProceed",
+      finished: true,
+    };
+    self.postMessage({ worker, progress });
+    return;
+  }
+
+  console.log(`Completed in: ${parseInt(performance.now() - start_time)}`);
+};